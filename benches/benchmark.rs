@@ -1,4 +1,4 @@
-use atomic_arena::{Arena, Key};
+use atomic_arena::{Arena, Key, SingleThreadArena};
 use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
 use rand::{prelude::SliceRandom, thread_rng};
 
@@ -16,6 +16,36 @@ fn benchmark(c: &mut Criterion) {
 				BatchSize::SmallInput,
 			);
 		});
+		c.bench_with_input(
+			BenchmarkId::new("reserve slots (cloned controller)", size),
+			&size,
+			|b, size| {
+				b.iter_batched(
+					|| Arena::<()>::new(*size),
+					|arena| {
+						for _ in 0..*size {
+							arena.controller().try_reserve().unwrap();
+						}
+					},
+					BatchSize::SmallInput,
+				);
+			},
+		);
+		c.bench_with_input(
+			BenchmarkId::new("reserve slots (borrowed controller)", size),
+			&size,
+			|b, size| {
+				b.iter_batched(
+					|| Arena::<()>::new(*size),
+					|arena| {
+						for _ in 0..*size {
+							arena.controller_ref().try_reserve().unwrap();
+						}
+					},
+					BatchSize::SmallInput,
+				);
+			},
+		);
 		c.bench_with_input(BenchmarkId::new("insert", size), &size, |b, size| {
 			b.iter_batched(
 				|| Arena::new(*size),
@@ -27,6 +57,21 @@ fn benchmark(c: &mut Criterion) {
 				BatchSize::SmallInput,
 			);
 		});
+		c.bench_with_input(
+			BenchmarkId::new("insert (single thread)", size),
+			&size,
+			|b, size| {
+				b.iter_batched(
+					|| SingleThreadArena::new(*size),
+					|mut arena| {
+						for i in 0..*size {
+							arena.insert(i).unwrap();
+						}
+					},
+					BatchSize::SmallInput,
+				);
+			},
+		);
 		c.bench_with_input(BenchmarkId::new("remove", size), &size, |b, size| {
 			b.iter_batched(
 				|| {
@@ -45,8 +90,158 @@ fn benchmark(c: &mut Criterion) {
 				BatchSize::SmallInput,
 			)
 		});
+		c.bench_with_input(
+			BenchmarkId::new("remove (single thread)", size),
+			&size,
+			|b, size| {
+				b.iter_batched(
+					|| {
+						let mut arena = SingleThreadArena::new(*size * 2);
+						let mut indices: Vec<Key> =
+							(0..*size * 2).map(|i| arena.insert(i).unwrap()).collect();
+						let indices_to_remove =
+							Vec::from(indices.partial_shuffle(&mut thread_rng(), *size).0);
+						(arena, indices_to_remove)
+					},
+					|(mut arena, keys_to_remove)| {
+						for key in keys_to_remove {
+							arena.remove(key).unwrap();
+						}
+					},
+					BatchSize::SmallInput,
+				)
+			},
+		);
+		c.bench_with_input(BenchmarkId::new("iter values", size), &size, |b, size| {
+			let mut arena = Arena::new(*size);
+			for i in 0..*size {
+				arena.insert(i).unwrap();
+			}
+			b.iter(|| arena.iter().map(|(_, value)| *value).sum::<usize>());
+		});
+		c.bench_with_input(
+			BenchmarkId::new("iter values (slot order)", size),
+			&size,
+			|b, size| {
+				let mut arena = Arena::new(*size);
+				for i in 0..*size {
+					arena.insert(i).unwrap();
+				}
+				b.iter(|| arena.iter_ordered().map(|(_, value)| *value).sum::<usize>());
+			},
+		);
+		c.bench_with_input(BenchmarkId::new("get random", size), &size, |b, size| {
+			let mut arena = Arena::new(*size);
+			let mut keys: Vec<Key> = (0..*size).map(|i| arena.insert(i).unwrap()).collect();
+			keys.shuffle(&mut thread_rng());
+			b.iter(|| {
+				let mut sum = 0;
+				for key in &keys {
+					sum += arena[*key];
+				}
+				sum
+			});
+		});
+		c.bench_with_input(
+			BenchmarkId::new("get random (prefetch)", size),
+			&size,
+			|b, size| {
+				let mut arena = Arena::new(*size);
+				let mut keys: Vec<Key> = (0..*size).map(|i| arena.insert(i).unwrap()).collect();
+				keys.shuffle(&mut thread_rng());
+				b.iter(|| {
+					let mut sum = 0;
+					for (i, key) in keys.iter().enumerate() {
+						if let Some(next_key) = keys.get(i + 4) {
+							arena.prefetch(*next_key);
+						}
+						sum += arena[*key];
+					}
+					sum
+				});
+			},
+		);
+		c.bench_with_input(BenchmarkId::new("get repeated", size), &size, |b, size| {
+			let mut arena = Arena::new(*size);
+			let hot_keys: Vec<Key> = (0..*size)
+				.map(|i| arena.insert(i).unwrap())
+				.take(4)
+				.collect();
+			// access each hot key several times in a row
+			let keys: Vec<Key> = (0..*size).map(|i| hot_keys[i / 8 % 4]).collect();
+			b.iter(|| {
+				let mut sum = 0;
+				for key in &keys {
+					sum += arena[*key];
+				}
+				sum
+			});
+		});
+		c.bench_with_input(BenchmarkId::new("get disjoint", size), &size, |b, size| {
+			let mut arena = Arena::new(*size);
+			let keys: Vec<Key> = (0..*size).map(|i| arena.insert(i).unwrap()).collect();
+			b.iter(|| {
+				for pair in keys.chunks_exact(2) {
+					let [a, b] = arena.get_disjoint_mut([pair[0], pair[1]]).unwrap();
+					std::mem::swap(a, b);
+				}
+			});
+		});
+		c.bench_with_input(
+			BenchmarkId::new("get disjoint unchecked", size),
+			&size,
+			|b, size| {
+				let mut arena = Arena::new(*size);
+				let keys: Vec<Key> = (0..*size).map(|i| arena.insert(i).unwrap()).collect();
+				b.iter(|| {
+					for pair in keys.chunks_exact(2) {
+						// SAFETY: every key was returned by `insert` and nothing
+						// is ever removed, so they're all valid, and each insert
+						// used a different slot, so the two keys in a pair never
+						// refer to the same slot.
+						let [a, b] =
+							unsafe { arena.get_disjoint_unchecked_mut([pair[0], pair[1]]) };
+						std::mem::swap(a, b);
+					}
+				});
+			},
+		);
 	}
 
+	c.bench_function("retain (90% removed)", |b| {
+		b.iter_batched(
+			|| {
+				let mut arena = Arena::new(10_000);
+				for i in 0..10_000 {
+					arena.insert(i).unwrap();
+				}
+				arena
+			},
+			|mut arena| arena.retain(|i| i % 10 == 0),
+			BatchSize::SmallInput,
+		)
+	});
+	c.bench_function("remove each (90% removed)", |b| {
+		b.iter_batched(
+			|| {
+				let mut arena = Arena::new(10_000);
+				let keys: Vec<Key> = (0..10_000)
+					.filter_map(|i| {
+						let key = arena.insert(i).unwrap();
+						(i % 10 != 0).then_some(key)
+					})
+					.collect();
+				(arena, keys)
+			},
+			|(mut arena, keys)| {
+				for key in keys {
+					arena.remove(key);
+				}
+			},
+			BatchSize::SmallInput,
+		)
+	});
+
 	struct IterBenchmarkConfig {
 		len: usize,
 		capacity: usize,
@@ -0,0 +1,100 @@
+//! Change tracking for an [`Arena`](super::Arena).
+
+use std::{
+	collections::HashSet,
+	ops::{Deref, DerefMut},
+};
+
+use crate::Key;
+
+/// A marker for the state of an [`Arena`](super::Arena) at a
+/// point in time.
+///
+/// Returned by [`Arena::mark`](super::Arena::mark).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ChangeToken {
+	pub(super) version: usize,
+}
+
+/// The items inserted into and removed from an
+/// [`Arena`](super::Arena) since a [`ChangeToken`] was created.
+///
+/// Returned by [`Arena::changes_since`](super::Arena::changes_since).
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Changes {
+	/// The [`Key`]s of the items that were inserted and are still
+	/// in the arena, ordered by slot index.
+	pub inserted: Vec<Key>,
+	/// The [`Key`]s of the items that were in the arena when the
+	/// [`ChangeToken`] was created and have been removed since,
+	/// in the order they were removed.
+	pub removed: Vec<Key>,
+}
+
+/// The state an [`Arena`](super::Arena) needs to report
+/// [`Changes`].
+#[derive(Debug, Default)]
+pub(super) struct ChangeLog {
+	/// The version given to items inserted now. Incremented
+	/// every time a [`ChangeToken`] is created.
+	pub(super) version: usize,
+	/// The version of the most recent [`ChangeToken`], or `None`
+	/// if one has never been created.
+	pub(super) marked_version: Option<usize>,
+	/// The [`Key`]s of the items removed since the most recent
+	/// [`ChangeToken`] was created, along with the versions they
+	/// were inserted at.
+	pub(super) removed: Vec<(Key, usize)>,
+}
+
+impl ChangeLog {
+	/// Records that the item with the given [`Key`], which was
+	/// inserted at the given version, was removed if a
+	/// [`ChangeToken`] has been created.
+	pub(super) fn log_removal(&mut self, key: Key, inserted_version: usize) {
+		if self.marked_version.is_some() {
+			self.removed.push((key, inserted_version));
+		}
+	}
+}
+
+/// A mutable reference to an item in an [`Arena`](super::Arena)
+/// that records the item's [`Key`] as dirty when dropped.
+///
+/// Returned by [`Arena::get_mut_tracked`](super::Arena::get_mut_tracked).
+pub struct DirtyGuard<'a, T> {
+	key: Key,
+	data: &'a mut T,
+	dirty: &'a mut HashSet<Key>,
+}
+
+impl<'a, T> DirtyGuard<'a, T> {
+	pub(super) fn new(key: Key, data: &'a mut T, dirty: &'a mut HashSet<Key>) -> Self {
+		Self { key, data, dirty }
+	}
+
+	/// Returns the [`Key`] of the item.
+	pub fn key(&self) -> Key {
+		self.key
+	}
+}
+
+impl<T> Deref for DirtyGuard<'_, T> {
+	type Target = T;
+
+	fn deref(&self) -> &Self::Target {
+		self.data
+	}
+}
+
+impl<T> DerefMut for DirtyGuard<'_, T> {
+	fn deref_mut(&mut self) -> &mut Self::Target {
+		self.data
+	}
+}
+
+impl<T> Drop for DirtyGuard<'_, T> {
+	fn drop(&mut self) {
+		self.dirty.insert(self.key);
+	}
+}
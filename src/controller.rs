@@ -1,9 +1,13 @@
-use std::sync::{
-	atomic::{AtomicBool, AtomicUsize, Ordering},
-	Arc,
+use std::{
+	alloc::{alloc_zeroed, handle_alloc_error, Layout},
+	ops::Range,
+	sync::{
+		atomic::{AtomicBool, AtomicU8, AtomicUsize, Ordering},
+		Arc,
+	},
 };
 
-use crate::{ArenaFull, Key};
+use crate::{error::CapacityError, ArenaFull, Key, ReservationOrder};
 
 /// Represents that a [`ControllerSlot`] does not have a free slot
 /// after it.
@@ -13,35 +17,410 @@ use crate::{ArenaFull, Key};
 /// absence of a next free slot.
 const NO_NEXT_FREE_SLOT: usize = usize::MAX;
 
+/// Set in [`ControllerSlot::state`] if the slot can be reserved.
+const FREE: u8 = 1;
+/// Set in [`ControllerSlot::state`] if the slot's index is in the
+/// free list (or free queue).
+///
+/// A slot reserved by [`Controller::try_reserve_in_range`] stays
+/// in the free list without being free. It's skipped when it
+/// reaches the front of the list, unless it's been freed again
+/// by then, in which case it doesn't need to be pushed a
+/// second time.
+const LISTED: u8 = 2;
+/// Set in [`ControllerSlot::state`] for every slot except the ones
+/// that have never been reserved or added to the free list.
+const INITIALIZED: u8 = 4;
+
+/// A slot that's all zeros has never been reserved. It's free, but
+/// it isn't in the free list. It's reserved once
+/// [`ControllerInner::next_unused_slot_index`] reaches it, unless a
+/// reservation in a range claims it first.
 #[derive(Debug)]
 struct ControllerSlot {
-	free: AtomicBool,
+	/// A combination of the [`FREE`], [`LISTED`] and [`INITIALIZED`]
+	/// flags.
+	state: AtomicU8,
+	/// Set once the slot has been retired. A retired slot is never
+	/// put back on the free list.
+	retired: AtomicBool,
+	/// Set the first time the slot is reserved.
+	used: AtomicBool,
 	generation: AtomicUsize,
 	next_free_slot_index: AtomicUsize,
 }
 
+impl ControllerSlot {
+	fn is_free(&self) -> bool {
+		let state = self.state.load(Ordering::SeqCst);
+		state & FREE != 0 || state == 0
+	}
+
+	/// Marks a slot as free and returns `true` if it needs to be
+	/// added to the free list.
+	fn mark_free(&self) -> bool {
+		self.state.fetch_or(FREE | LISTED, Ordering::SeqCst) & LISTED == 0
+	}
+
+	/// Marks a slot that was just taken off the free list as no
+	/// longer listed and returns `true` if it was free, in which
+	/// case it's now reserved.
+	fn take_from_list(&self) -> bool {
+		self.state.swap(INITIALIZED, Ordering::SeqCst) & FREE != 0
+	}
+
+	/// Reserves a free slot without taking it off the free list.
+	/// Returns `false` if the slot isn't free.
+	fn claim(&self) -> bool {
+		self.state
+			.compare_exchange(
+				FREE | LISTED | INITIALIZED,
+				LISTED | INITIALIZED,
+				Ordering::SeqCst,
+				Ordering::SeqCst,
+			)
+			.is_ok()
+	}
+
+	/// Reserves a slot that has never been reserved or added to the
+	/// free list. Returns `false` otherwise.
+	fn claim_unused(&self) -> bool {
+		self.state
+			.compare_exchange(0, INITIALIZED, Ordering::SeqCst, Ordering::SeqCst)
+			.is_ok()
+	}
+}
+
+/// Allocates `capacity` [`ControllerSlot`]s without writing to
+/// them, so the memory doesn't have to be touched until each slot
+/// is reserved for the first time.
+fn zeroed_slots(capacity: usize) -> Vec<ControllerSlot> {
+	if capacity == 0 {
+		return Vec::new();
+	}
+	let layout = Layout::array::<ControllerSlot>(capacity).expect("capacity overflow");
+	// SAFETY: the layout isn't zero-sized, since the capacity is
+	// at least 1 and a `ControllerSlot` isn't zero-sized.
+	let ptr = unsafe { alloc_zeroed(layout) }.cast::<ControllerSlot>();
+	if ptr.is_null() {
+		handle_alloc_error(layout);
+	}
+	// SAFETY: the pointer was allocated by the global allocator
+	// with the same layout a `Vec` would use for `capacity` slots,
+	// and every field of a `ControllerSlot` is an atomic integer or
+	// bool, so all zeros is a valid value for each slot.
+	unsafe { Vec::from_raw_parts(ptr, capacity, capacity) }
+}
+
+#[derive(Debug)]
+struct FreeQueueCell {
+	sequence: AtomicUsize,
+	slot_index: AtomicUsize,
+}
+
+/// A bounded queue of free slot indices, used instead of the
+/// free list when reserving slots in [`ReservationOrder::Fifo`].
+///
+/// This is a bounded MPMC queue (as described by Dmitry Vyukov).
+/// Each cell has a sequence number that tells pushers and poppers
+/// whether it's their turn to use the cell. Since each slot can
+/// only be in the queue once, the queue can never hold more than
+/// `capacity` indices.
+#[derive(Debug)]
+struct FreeQueue {
+	cells: Vec<FreeQueueCell>,
+	push_position: AtomicUsize,
+	pop_position: AtomicUsize,
+}
+
+impl FreeQueue {
+	/// Creates a queue containing every slot index in order.
+	fn new(capacity: usize) -> Self {
+		Self::with_slot_indices(capacity, (0..capacity).collect())
+	}
+
+	/// Creates a queue that can hold `capacity` indices and
+	/// initially contains the given slot indices.
+	fn with_slot_indices(capacity: usize, slot_indices: Vec<usize>) -> Self {
+		let len = slot_indices.len();
+		let mut slot_indices = slot_indices.into_iter();
+		Self {
+			cells: (0..capacity)
+				.map(|i| match slot_indices.next() {
+					Some(slot_index) => FreeQueueCell {
+						sequence: AtomicUsize::new(i + 1),
+						slot_index: AtomicUsize::new(slot_index),
+					},
+					None => FreeQueueCell {
+						sequence: AtomicUsize::new(i),
+						slot_index: AtomicUsize::new(0),
+					},
+				})
+				.collect(),
+			push_position: AtomicUsize::new(len),
+			pop_position: AtomicUsize::new(0),
+		}
+	}
+
+	fn push(&self, slot_index: usize) {
+		loop {
+			let position = self.push_position.load(Ordering::SeqCst);
+			let cell = &self.cells[position % self.cells.len()];
+			// if the sequence is behind the position, a popper hasn't
+			// finished with this cell yet, so we have to wait for it
+			if cell.sequence.load(Ordering::SeqCst) != position {
+				continue;
+			}
+			if self
+				.push_position
+				.compare_exchange_weak(position, position + 1, Ordering::SeqCst, Ordering::SeqCst)
+				.is_ok()
+			{
+				cell.slot_index.store(slot_index, Ordering::SeqCst);
+				cell.sequence.store(position + 1, Ordering::SeqCst);
+				return;
+			}
+		}
+	}
+
+	/// Returns the slot indices currently in the queue, in the
+	/// order they'll be popped, without popping them.
+	fn slot_indices(&self) -> Vec<usize> {
+		let pop_position = self.pop_position.load(Ordering::SeqCst);
+		let push_position = self.push_position.load(Ordering::SeqCst);
+		let mut slot_indices = vec![];
+		for position in pop_position..push_position.min(pop_position + self.cells.len()) {
+			let cell = &self.cells[position % self.cells.len()];
+			// stop at cells that are still being pushed to or popped from
+			if cell.sequence.load(Ordering::SeqCst) != position + 1 {
+				break;
+			}
+			slot_indices.push(cell.slot_index.load(Ordering::SeqCst));
+		}
+		slot_indices
+	}
+
+	fn pop(&self) -> Option<usize> {
+		if self.cells.is_empty() {
+			return None;
+		}
+		loop {
+			let position = self.pop_position.load(Ordering::SeqCst);
+			let cell = &self.cells[position % self.cells.len()];
+			let sequence = cell.sequence.load(Ordering::SeqCst);
+			// if the sequence is behind the position, nothing has been
+			// pushed to this cell yet, so the queue is empty
+			if (sequence as isize).wrapping_sub((position + 1) as isize) < 0 {
+				return None;
+			}
+			if sequence != position + 1 {
+				continue;
+			}
+			if self
+				.pop_position
+				.compare_exchange_weak(position, position + 1, Ordering::SeqCst, Ordering::SeqCst)
+				.is_ok()
+			{
+				let slot_index = cell.slot_index.load(Ordering::SeqCst);
+				cell.sequence
+					.store(position + self.cells.len(), Ordering::SeqCst);
+				return Some(slot_index);
+			}
+		}
+	}
+}
+
 /// The shared state for all [`Controller`]s for an [`Arena`](super::Arena).
 #[derive(Debug)]
 struct ControllerInner {
 	slots: Vec<ControllerSlot>,
 	first_free_slot_index: AtomicUsize,
+	/// The head of a second free list for slots that can only be
+	/// reserved with [`Controller::try_reserve_priority`].
+	first_free_priority_slot_index: AtomicUsize,
+	/// The slots that belong to the priority free list.
+	priority_slots: Range<usize>,
+	/// Slots at or past this index have never been reserved and
+	/// aren't in the free list. They're reserved in order once the
+	/// free list is empty. This is only ever less than the capacity
+	/// for controllers created with [`new_lazy`](Self::new_lazy).
+	next_unused_slot_index: AtomicUsize,
+	/// The queue of free slots, used instead of the free list
+	/// if the reservation order is [`ReservationOrder::Fifo`].
+	free_queue: Option<FreeQueue>,
+	successful_reservations: AtomicUsize,
+	reservation_retries: AtomicUsize,
+	/// The number of items inserted into the arena.
+	inserts_completed: AtomicUsize,
 }
 
 impl ControllerInner {
-	fn new(capacity: usize) -> Self {
+	fn new(capacity: usize, reservation_order: ReservationOrder) -> Self {
+		Self::with_free_slots(Self::free_slots(capacity).collect(), reservation_order)
+	}
+
+	/// Like [`new`](Self::new), but returns an error instead of
+	/// aborting if the slots can't be allocated.
+	fn try_new(capacity: usize) -> Result<Self, CapacityError> {
+		let mut slots = Vec::new();
+		slots
+			.try_reserve_exact(capacity)
+			.map_err(|_| CapacityError)?;
+		slots.extend(Self::free_slots(capacity));
+		Ok(Self::with_free_slots(slots, ReservationOrder::Lifo))
+	}
+
+	/// Returns `capacity` free slots, each linked to the next one
+	/// in the free list.
+	fn free_slots(capacity: usize) -> impl Iterator<Item = ControllerSlot> {
+		(0..capacity).map(move |i| ControllerSlot {
+			state: AtomicU8::new(FREE | LISTED | INITIALIZED),
+			retired: AtomicBool::new(false),
+			used: AtomicBool::new(false),
+			generation: AtomicUsize::new(0),
+			next_free_slot_index: AtomicUsize::new(if i < capacity - 1 {
+				i + 1
+			} else {
+				NO_NEXT_FREE_SLOT
+			}),
+		})
+	}
+
+	/// Creates the controller state for the slots returned by
+	/// [`free_slots`](Self::free_slots).
+	fn with_free_slots(slots: Vec<ControllerSlot>, reservation_order: ReservationOrder) -> Self {
+		let capacity = slots.len();
+		Self {
+			slots,
+			first_free_slot_index: AtomicUsize::new(if capacity > 0 {
+				0
+			} else {
+				NO_NEXT_FREE_SLOT
+			}),
+			first_free_priority_slot_index: AtomicUsize::new(NO_NEXT_FREE_SLOT),
+			priority_slots: 0..0,
+			next_unused_slot_index: AtomicUsize::new(capacity),
+			free_queue: match reservation_order {
+				ReservationOrder::Lifo => None,
+				ReservationOrder::Fifo => Some(FreeQueue::new(capacity)),
+			},
+			successful_reservations: AtomicUsize::new(0),
+			reservation_retries: AtomicUsize::new(0),
+			inserts_completed: AtomicUsize::new(0),
+		}
+	}
+
+	/// Creates the controller state for `capacity` free slots
+	/// without writing to any of them.
+	fn new_lazy(capacity: usize) -> Self {
+		Self {
+			slots: zeroed_slots(capacity),
+			first_free_slot_index: AtomicUsize::new(NO_NEXT_FREE_SLOT),
+			first_free_priority_slot_index: AtomicUsize::new(NO_NEXT_FREE_SLOT),
+			priority_slots: 0..0,
+			next_unused_slot_index: AtomicUsize::new(0),
+			free_queue: None,
+			successful_reservations: AtomicUsize::new(0),
+			reservation_retries: AtomicUsize::new(0),
+			inserts_completed: AtomicUsize::new(0),
+		}
+	}
+
+	fn new_full(capacity: usize) -> Self {
 		Self {
 			slots: (0..capacity)
-				.map(|i| ControllerSlot {
-					free: AtomicBool::new(true),
+				.map(|_| ControllerSlot {
+					state: AtomicU8::new(INITIALIZED),
+					retired: AtomicBool::new(false),
+					used: AtomicBool::new(true),
 					generation: AtomicUsize::new(0),
-					next_free_slot_index: AtomicUsize::new(if i < capacity - 1 {
-						i + 1
-					} else {
-						NO_NEXT_FREE_SLOT
-					}),
+					next_free_slot_index: AtomicUsize::new(NO_NEXT_FREE_SLOT),
 				})
 				.collect(),
-			first_free_slot_index: AtomicUsize::new(0),
+			first_free_slot_index: AtomicUsize::new(NO_NEXT_FREE_SLOT),
+			first_free_priority_slot_index: AtomicUsize::new(NO_NEXT_FREE_SLOT),
+			priority_slots: 0..0,
+			next_unused_slot_index: AtomicUsize::new(capacity),
+			free_queue: None,
+			successful_reservations: AtomicUsize::new(0),
+			reservation_retries: AtomicUsize::new(0),
+			inserts_completed: AtomicUsize::new(0),
+		}
+	}
+
+	/// Creates the controller state for `capacity` free slots,
+	/// where the last `priority_capacity` slots are kept on a
+	/// separate free list.
+	fn with_priority_lane(capacity: usize, priority_capacity: usize) -> Self {
+		let normal_capacity = capacity - priority_capacity;
+		let mut inner = Self::new(capacity, ReservationOrder::Lifo);
+		if priority_capacity > 0 {
+			// split the free list into two chains
+			if normal_capacity > 0 {
+				inner.slots[normal_capacity - 1]
+					.next_free_slot_index
+					.store(NO_NEXT_FREE_SLOT, Ordering::SeqCst);
+			} else {
+				inner
+					.first_free_slot_index
+					.store(NO_NEXT_FREE_SLOT, Ordering::SeqCst);
+			}
+			inner
+				.first_free_priority_slot_index
+				.store(normal_capacity, Ordering::SeqCst);
+		}
+		inner.priority_slots = normal_capacity..capacity;
+		inner
+	}
+
+	/// Creates the controller state for slots with the given
+	/// generations, where only the given slots are free and
+	/// will be reserved in the given order.
+	fn from_parts(
+		generations: &[usize],
+		free_slot_indices: &[usize],
+		reservation_order: ReservationOrder,
+	) -> Self {
+		let capacity = generations.len();
+		let mut next_free_slot_indices = vec![NO_NEXT_FREE_SLOT; capacity];
+		for pair in free_slot_indices.windows(2) {
+			next_free_slot_indices[pair[0]] = pair[1];
+		}
+		let mut states = vec![INITIALIZED; capacity];
+		for index in free_slot_indices {
+			states[*index] = FREE | LISTED | INITIALIZED;
+		}
+		Self {
+			slots: (0..capacity)
+				.map(|i| ControllerSlot {
+					state: AtomicU8::new(states[i]),
+					retired: AtomicBool::new(false),
+					// there's no record of which free slots have been
+					// used, so only count the slots that are in use now
+					used: AtomicBool::new(states[i] == INITIALIZED),
+					generation: AtomicUsize::new(generations[i]),
+					next_free_slot_index: AtomicUsize::new(next_free_slot_indices[i]),
+				})
+				.collect(),
+			first_free_slot_index: AtomicUsize::new(
+				free_slot_indices
+					.first()
+					.copied()
+					.unwrap_or(NO_NEXT_FREE_SLOT),
+			),
+			first_free_priority_slot_index: AtomicUsize::new(NO_NEXT_FREE_SLOT),
+			priority_slots: 0..0,
+			next_unused_slot_index: AtomicUsize::new(capacity),
+			free_queue: match reservation_order {
+				ReservationOrder::Lifo => None,
+				ReservationOrder::Fifo => Some(FreeQueue::with_slot_indices(
+					capacity,
+					free_slot_indices.to_vec(),
+				)),
+			},
+			successful_reservations: AtomicUsize::new(0),
+			reservation_retries: AtomicUsize::new(0),
+			inserts_completed: AtomicUsize::new(0),
 		}
 	}
 
@@ -49,22 +428,191 @@ impl ControllerInner {
 		self.slots.len()
 	}
 
+	fn reservation_order(&self) -> ReservationOrder {
+		match &self.free_queue {
+			Some(_) => ReservationOrder::Fifo,
+			None => ReservationOrder::Lifo,
+		}
+	}
+
+	fn grow(&mut self, additional: usize) {
+		let old_capacity = self.capacity();
+		let new_capacity = old_capacity + additional;
+		self.add_unused_slots_to_free_list();
+		*self.next_unused_slot_index.get_mut() = new_capacity;
+		if let Some(free_queue) = &mut self.free_queue {
+			// the new slots go to the back of the queue
+			let mut free_slot_indices = vec![];
+			while let Some(index) = free_queue.pop() {
+				free_slot_indices.push(index);
+			}
+			free_slot_indices.extend(old_capacity..new_capacity);
+			*free_queue = FreeQueue::with_slot_indices(new_capacity, free_slot_indices);
+		}
+		let first_free_slot_index = self.first_free_slot_index.load(Ordering::SeqCst);
+		let using_free_list = self.free_queue.is_none();
+		self.slots
+			.extend((old_capacity..new_capacity).map(|i| ControllerSlot {
+				state: AtomicU8::new(FREE | LISTED | INITIALIZED),
+				retired: AtomicBool::new(false),
+				used: AtomicBool::new(false),
+				generation: AtomicUsize::new(0),
+				next_free_slot_index: AtomicUsize::new(if !using_free_list {
+					NO_NEXT_FREE_SLOT
+				} else if i < new_capacity - 1 {
+					i + 1
+				} else {
+					first_free_slot_index
+				}),
+			}));
+		// the new slots go to the front of the free list
+		if using_free_list && additional > 0 {
+			self.first_free_slot_index
+				.store(old_capacity, Ordering::SeqCst);
+		}
+	}
+
+	/// Moves the slots that have never been reserved to the end of
+	/// the free list, keeping the order they'd be reserved in.
+	fn add_unused_slots_to_free_list(&mut self) {
+		let next_unused_slot_index = *self.next_unused_slot_index.get_mut();
+		let capacity = self.capacity();
+		*self.next_unused_slot_index.get_mut() = capacity;
+		// skip the slots that were reserved in a range
+		let unused_slot_indices: Vec<usize> = (next_unused_slot_index..capacity)
+			.filter(|&index| *self.slots[index].state.get_mut() == 0)
+			.collect();
+		let first_unused_slot_index = match unused_slot_indices.first() {
+			Some(&index) => index,
+			None => return,
+		};
+		for (i, &index) in unused_slot_indices.iter().enumerate() {
+			let slot = &mut self.slots[index];
+			*slot.state.get_mut() = FREE | LISTED | INITIALIZED;
+			*slot.next_free_slot_index.get_mut() = unused_slot_indices
+				.get(i + 1)
+				.copied()
+				.unwrap_or(NO_NEXT_FREE_SLOT);
+		}
+		match self.walk_free_list(&self.first_free_slot_index).last() {
+			Some(&last) => {
+				*self.slots[last].next_free_slot_index.get_mut() = first_unused_slot_index
+			}
+			None => *self.first_free_slot_index.get_mut() = first_unused_slot_index,
+		}
+	}
+
+	fn memory_usage(&self) -> usize {
+		let free_queue_size = match &self.free_queue {
+			Some(free_queue) => free_queue.cells.capacity() * std::mem::size_of::<FreeQueueCell>(),
+			None => 0,
+		};
+		std::mem::size_of::<Self>()
+			+ self.slots.capacity() * std::mem::size_of::<ControllerSlot>()
+			+ free_queue_size
+	}
+
 	fn len(&self) -> usize {
+		self.slots.iter().filter(|slot| !slot.is_free()).count()
+	}
+
+	fn is_free(&self, index: usize) -> bool {
+		self.slots[index].is_free()
+	}
+
+	fn normal_free_count(&self) -> usize {
 		self.slots
 			.iter()
-			.filter(|slot| !slot.free.load(Ordering::SeqCst))
+			.enumerate()
+			.filter(|(index, slot)| !self.priority_slots.contains(index) && slot.is_free())
 			.count()
 	}
 
-	fn try_reserve(&self) -> Result<Key, ArenaFull> {
+	fn try_reserve(&self) -> Result<(Key, bool), ArenaFull> {
+		let key = self.pop_free_slot().or_else(|_| self.take_unused_slot())?;
+		Ok(self.record_reservation(key))
+	}
+
+	fn try_reserve_priority(&self) -> Result<Key, ArenaFull> {
+		let key = self.pop_any_free_slot()?;
+		Ok(self.record_reservation(key).0)
+	}
+
+	/// Counts a reservation in the contention stats and marks the
+	/// slot as used. Returns the key along with whether the slot
+	/// had been used before.
+	fn record_reservation(&self, key: Key) -> (Key, bool) {
+		self.successful_reservations.fetch_add(1, Ordering::SeqCst);
+		let recycled = self.slots[key.index].used.swap(true, Ordering::SeqCst);
+		(key, recycled)
+	}
+
+	/// Takes the next free slot off the normal free list, then
+	/// from the slots that have never been reserved, then from the
+	/// priority free list.
+	fn pop_any_free_slot(&self) -> Result<Key, ArenaFull> {
+		self.pop_free_slot()
+			.or_else(|_| self.take_unused_slot())
+			.or_else(|_| self.pop_from_free_list(&self.first_free_priority_slot_index))
+	}
+
+	/// Takes the next free slot off the normal free list, or the
+	/// priority free list if the normal one is empty, leaving the
+	/// slots that have never been reserved alone.
+	fn pop_listed_free_slot(&self) -> Result<Key, ArenaFull> {
+		self.pop_free_slot()
+			.or_else(|_| self.pop_from_free_list(&self.first_free_priority_slot_index))
+	}
+
+	/// Takes the lowest slot that has never been reserved.
+	fn take_unused_slot(&self) -> Result<Key, ArenaFull> {
 		loop {
-			let first_free_slot_index = self.first_free_slot_index.load(Ordering::SeqCst);
+			let index = self
+				.next_unused_slot_index
+				.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |index| {
+					(index < self.capacity()).then(|| index + 1)
+				})
+				.map_err(|_| ArenaFull)?;
+			let slot = &self.slots[index];
+			// skip slots that were already reserved in a range
+			if slot.claim_unused() {
+				return Ok(Key {
+					index,
+					generation: slot.generation.load(Ordering::SeqCst),
+				});
+			}
+		}
+	}
+
+	/// Takes the next free slot off the free list without counting
+	/// it as a reservation in the contention stats.
+	fn pop_free_slot(&self) -> Result<Key, ArenaFull> {
+		if let Some(free_queue) = &self.free_queue {
+			loop {
+				let index = free_queue.pop().ok_or(ArenaFull)?;
+				let slot = &self.slots[index];
+				// skip slots that were reserved while they were
+				// still in the queue
+				if slot.take_from_list() {
+					return Ok(Key {
+						index,
+						generation: slot.generation.load(Ordering::SeqCst),
+					});
+				}
+			}
+		}
+		self.pop_from_free_list(&self.first_free_slot_index)
+	}
+
+	/// Takes the next free slot off the free list with the given head.
+	fn pop_from_free_list(&self, head: &AtomicUsize) -> Result<Key, ArenaFull> {
+		loop {
+			let first_free_slot_index = head.load(Ordering::SeqCst);
 			if first_free_slot_index == NO_NEXT_FREE_SLOT {
 				return Err(ArenaFull);
 			}
 			let slot = &self.slots[first_free_slot_index];
-			if self
-				.first_free_slot_index
+			if head
 				.compare_exchange_weak(
 					first_free_slot_index,
 					slot.next_free_slot_index.load(Ordering::SeqCst),
@@ -73,25 +621,215 @@ impl ControllerInner {
 				)
 				.is_ok()
 			{
-				slot.free.store(false, Ordering::SeqCst);
-				return Ok(Key {
-					index: first_free_slot_index,
+				// skip slots that were reserved while they were
+				// still in the list
+				if slot.take_from_list() {
+					return Ok(Key {
+						index: first_free_slot_index,
+						generation: slot.generation.load(Ordering::SeqCst),
+					});
+				}
+				continue;
+			}
+			self.reservation_retries.fetch_add(1, Ordering::SeqCst);
+		}
+	}
+
+	fn try_reserve_in_range(&self, range: Range<usize>) -> Result<Key, ArenaFull> {
+		// claim the first free slot in the range. it stays where
+		// it is, in the free list or the unused slots, so no other
+		// slots are disturbed, and it'll be skipped once it's reached
+		let end = range.end.min(self.capacity());
+		for index in range.start..end {
+			if self.priority_slots.contains(&index) {
+				continue;
+			}
+			let slot = &self.slots[index];
+			if slot.claim() || slot.claim_unused() {
+				let key = Key {
+					index,
 					generation: slot.generation.load(Ordering::SeqCst),
-				});
+				};
+				return Ok(self.record_reservation(key).0);
 			}
 		}
+		Err(ArenaFull)
 	}
 
-	fn free(&self, index: usize) {
+	fn compact_free_list(&self) {
+		// take every slot off the free list, then put them back
+		// so that the lowest slot indices are reserved first
+		// slots that have never been reserved already come after
+		// the rest in order, so they can stay where they are
+		let mut free_slot_indices = vec![];
+		while let Ok(key) = self.pop_listed_free_slot() {
+			free_slot_indices.push(key.index);
+		}
+		free_slot_indices.sort_unstable();
+		if self.free_queue.is_none() {
+			// the free list is a stack, so the slots need to be
+			// pushed in reverse order
+			free_slot_indices.reverse();
+		}
+		for index in free_slot_indices {
+			self.push_free_slot(index);
+		}
+	}
+
+	fn reset_free_slot_generations(&self) -> Vec<usize> {
+		// take every slot off the free list so no one can reserve
+		// them while their generations are changing, then put them
+		// back in their original order
+		// slots that have never been reserved keep their generations
+		let mut free_slot_indices = vec![];
+		while let Ok(key) = self.pop_listed_free_slot() {
+			free_slot_indices.push(key.index);
+		}
+		for index in &free_slot_indices {
+			self.slots[*index].generation.store(0, Ordering::SeqCst);
+		}
+		let push_order: Vec<usize> = if self.free_queue.is_none() {
+			free_slot_indices.iter().rev().copied().collect()
+		} else {
+			free_slot_indices.clone()
+		};
+		for index in push_order {
+			self.push_free_slot(index);
+		}
+		free_slot_indices
+	}
+
+	fn free_list_order(&self) -> Vec<usize> {
+		let mut order = match &self.free_queue {
+			Some(free_queue) => free_queue.slot_indices(),
+			None => self.walk_free_list(&self.first_free_slot_index),
+		};
+		order.extend(self.next_unused_slot_index.load(Ordering::SeqCst)..self.capacity());
+		order.extend(self.walk_free_list(&self.first_free_priority_slot_index));
+		// slots reserved by `try_reserve_in_range` are still listed
+		order.retain(|&index| self.is_free(index));
+		order
+	}
+
+	/// Follows the free list with the given head and returns the
+	/// slot indices in it, stopping after `capacity` slots in case
+	/// the list has a cycle.
+	fn walk_free_list(&self, head: &AtomicUsize) -> Vec<usize> {
+		let mut order = vec![];
+		let mut index = head.load(Ordering::SeqCst);
+		while index != NO_NEXT_FREE_SLOT && order.len() < self.capacity() {
+			order.push(index);
+			index = self.slots[index]
+				.next_free_slot_index
+				.load(Ordering::SeqCst);
+		}
+		order
+	}
+
+	fn contention_stats(&self) -> ContentionStats {
+		ContentionStats {
+			successful_reservations: self.successful_reservations.load(Ordering::SeqCst),
+			reservation_retries: self.reservation_retries.load(Ordering::SeqCst),
+		}
+	}
+
+	fn retire(&self, index: usize) {
+		// bump the generation, but don't put the slot back on the
+		// free list, so it can never be reserved again
 		let slot = &self.slots[index];
-		slot.free.store(true, Ordering::SeqCst);
+		slot.retired.store(true, Ordering::SeqCst);
 		slot.generation.fetch_add(1, Ordering::SeqCst);
+	}
+
+	fn is_retired(&self, index: usize) -> bool {
+		self.slots[index].retired.load(Ordering::SeqCst)
+	}
+
+	fn free(&self, index: usize) {
+		if self.is_retired(index) {
+			return;
+		}
+		self.slots[index].generation.fetch_add(1, Ordering::SeqCst);
+		self.push_free_slot(index);
+	}
+
+	/// Like calling [`free`](Self::free) for each index in order,
+	/// but adds the slots to the free list all at once.
+	fn free_many(&self, indices: &[usize]) {
+		// the free queue and the priority free list need each slot
+		// to be pushed separately
+		if self.free_queue.is_some() || !self.priority_slots.is_empty() {
+			for &index in indices {
+				self.free(index);
+			}
+			return;
+		}
+		if indices.iter().any(|&index| self.is_retired(index)) {
+			for &index in indices {
+				self.free(index);
+			}
+			return;
+		}
+		// slots that are still listed don't need to be pushed again
+		let indices: Vec<usize> = indices
+			.iter()
+			.copied()
+			.filter(|&index| {
+				let slot = &self.slots[index];
+				slot.generation.fetch_add(1, Ordering::SeqCst);
+				slot.mark_free()
+			})
+			.collect();
+		let (first, last) = match (indices.first(), indices.last()) {
+			(Some(first), Some(last)) => (*first, *last),
+			_ => return,
+		};
+		// chain the slots together so the last one freed is reserved
+		// first, the same as if they were pushed one at a time
+		for pair in indices.windows(2) {
+			self.slots[pair[1]]
+				.next_free_slot_index
+				.store(pair[0], Ordering::SeqCst);
+		}
 		loop {
 			let first_free_slot_index = self.first_free_slot_index.load(Ordering::SeqCst);
-			slot.next_free_slot_index
+			self.slots[first]
+				.next_free_slot_index
 				.store(first_free_slot_index, Ordering::SeqCst);
 			if self
 				.first_free_slot_index
+				.compare_exchange_weak(
+					first_free_slot_index,
+					last,
+					Ordering::SeqCst,
+					Ordering::SeqCst,
+				)
+				.is_ok()
+			{
+				break;
+			}
+		}
+	}
+
+	fn push_free_slot(&self, index: usize) {
+		let slot = &self.slots[index];
+		if slot.retired.load(Ordering::SeqCst) || !slot.mark_free() {
+			return;
+		}
+		if let Some(free_queue) = &self.free_queue {
+			free_queue.push(index);
+			return;
+		}
+		let head = if self.priority_slots.contains(&index) {
+			&self.first_free_priority_slot_index
+		} else {
+			&self.first_free_slot_index
+		};
+		loop {
+			let first_free_slot_index = head.load(Ordering::SeqCst);
+			slot.next_free_slot_index
+				.store(first_free_slot_index, Ordering::SeqCst);
+			if head
 				.compare_exchange_weak(
 					first_free_slot_index,
 					index,
@@ -106,13 +844,73 @@ impl ControllerInner {
 	}
 }
 
+/// The result of [`Controller::reserve_report`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ReserveReport {
+	/// A [`Key`] was reserved.
+	Available(Key),
+	/// The arena is full, so reserving a [`Key`] would require
+	/// growing it.
+	WouldExceedCapacity,
+}
+
+/// Statistics about how often reservations from a [`Controller`]
+/// had to retry because of other threads, returned by
+/// [`Controller::contention_stats`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ContentionStats {
+	/// The total number of keys that have been reserved.
+	pub successful_reservations: usize,
+	/// The total number of times a reservation had to retry
+	/// because another thread changed the free list at the
+	/// same time.
+	pub reservation_retries: usize,
+}
+
 /// Manages [`Key`] reservations for an [`Arena`](super::Arena).
 #[derive(Debug, Clone)]
 pub struct Controller(Arc<ControllerInner>);
 
 impl Controller {
-	pub(crate) fn new(capacity: usize) -> Self {
-		Self(Arc::new(ControllerInner::new(capacity)))
+	pub(crate) fn new(capacity: usize, reservation_order: ReservationOrder) -> Self {
+		Self(Arc::new(ControllerInner::new(capacity, reservation_order)))
+	}
+
+	/// Like [`new`](Self::new) with the default reservation order,
+	/// but returns an error instead of aborting if the slots can't
+	/// be allocated.
+	pub(crate) fn try_new(capacity: usize) -> Result<Self, CapacityError> {
+		Ok(Self(Arc::new(ControllerInner::try_new(capacity)?)))
+	}
+
+	pub(crate) fn with_priority_lane(capacity: usize, priority_capacity: usize) -> Self {
+		Self(Arc::new(ControllerInner::with_priority_lane(
+			capacity,
+			priority_capacity,
+		)))
+	}
+
+	/// Creates a [`Controller`] whose slots aren't written to until
+	/// they're reserved for the first time.
+	pub(crate) fn new_lazy(capacity: usize) -> Self {
+		Self(Arc::new(ControllerInner::new_lazy(capacity)))
+	}
+
+	/// Creates a [`Controller`] with every slot already reserved.
+	pub(crate) fn new_full(capacity: usize) -> Self {
+		Self(Arc::new(ControllerInner::new_full(capacity)))
+	}
+
+	pub(crate) fn from_parts(
+		generations: &[usize],
+		free_slot_indices: &[usize],
+		reservation_order: ReservationOrder,
+	) -> Self {
+		Self(Arc::new(ControllerInner::from_parts(
+			generations,
+			free_slot_indices,
+			reservation_order,
+		)))
 	}
 
 	/// Returns the total capacity of the arena.
@@ -130,12 +928,205 @@ impl Controller {
 		self.len() == 0
 	}
 
+	/// Returns the number of slots that are free to be reserved.
+	pub fn free_count(&self) -> usize {
+		self.capacity() - self.len()
+	}
+
 	/// Tries to reserve a key for the [`Arena`](super::Arena).
+	///
+	/// This never uses slots set aside for
+	/// [`try_reserve_priority`](Self::try_reserve_priority).
 	pub fn try_reserve(&self) -> Result<Key, ArenaFull> {
+		self.0.try_reserve().map(|(key, _)| key)
+	}
+
+	/// Like [`try_reserve`](Self::try_reserve), but also returns
+	/// `true` if the slot has been used before.
+	///
+	/// A slot counts as used before if it's been reserved before,
+	/// regardless of its generation. For an
+	/// [`Arena`](super::Arena) created with
+	/// [`Arena::from_parts`](super::Arena::from_parts), only the
+	/// slots that weren't free count as used.
+	pub fn try_reserve_detailed(&self) -> Result<(Key, bool), ArenaFull> {
 		self.0.try_reserve()
 	}
 
+	/// Tries to reserve a key for the [`Arena`](super::Arena),
+	/// including from the slots set aside for priority reservations
+	/// by [`Arena::new_with_reserved_lane`](super::Arena::new_with_reserved_lane).
+	///
+	/// Other slots are used first, so the priority slots are only
+	/// used when the rest of the arena is full.
+	pub fn try_reserve_priority(&self) -> Result<Key, ArenaFull> {
+		self.0.try_reserve_priority()
+	}
+
+	/// Tries to reserve a key for the [`Arena`](super::Arena),
+	/// reporting whether the arena would need to grow to make
+	/// the reservation.
+	pub fn reserve_report(&self) -> ReserveReport {
+		match self.try_reserve() {
+			Ok(key) => ReserveReport::Available(key),
+			Err(ArenaFull) => ReserveReport::WouldExceedCapacity,
+		}
+	}
+
+	/// Tries to reserve a key for the [`Arena`](super::Arena) whose
+	/// slot index falls within `range`.
+	///
+	/// The lowest free slot index in the range is used. Other free
+	/// slots aren't disturbed, so this is safe to call while other
+	/// threads are reserving keys, and the reservation order of
+	/// the remaining free slots stays the same.
+	pub fn try_reserve_in_range(&self, range: Range<usize>) -> Result<Key, ArenaFull> {
+		self.0.try_reserve_in_range(range)
+	}
+
+	/// Returns the indices of the free slots in the order they'll
+	/// be reserved.
+	///
+	/// Slots set aside for
+	/// [`try_reserve_priority`](Self::try_reserve_priority) come
+	/// last. This is meant for debugging, so if other threads are
+	/// reserving or freeing slots at the same time, the result may
+	/// be incomplete. If the free list contains a cycle, this
+	/// stops after `capacity` slots.
+	pub fn free_list_order(&self) -> Vec<usize> {
+		self.0.free_list_order()
+	}
+
+	/// Returns statistics about reservations made with this
+	/// [`Controller`] and every clone of it.
+	///
+	/// Keys reserved by the arena itself, such as when inserting
+	/// items, are included.
+	pub fn contention_stats(&self) -> ContentionStats {
+		self.0.contention_stats()
+	}
+
+	/// Returns the number of items that have been inserted into
+	/// the [`Arena`](super::Arena) since it was created.
+	///
+	/// Unlike [`len`](super::Arena::len), this never decreases,
+	/// since removing items doesn't affect it. Items an
+	/// [`Arena`](super::Arena) is created with, such as by
+	/// [`Arena::filled`](super::Arena::filled), aren't counted.
+	pub fn inserts_completed(&self) -> usize {
+		self.0.inserts_completed.load(Ordering::SeqCst)
+	}
+
+	/// Returns the number of bytes the shared controller state
+	/// needs for each slot when using the default reservation order.
+	pub(crate) fn memory_per_slot() -> usize {
+		std::mem::size_of::<ControllerSlot>()
+	}
+
+	/// Returns the number of bytes allocated for the shared
+	/// controller state, including the reference counts.
+	pub(crate) fn memory_usage(&self) -> usize {
+		// the Arc allocation holds the strong and weak counts
+		// alongside the inner state
+		2 * std::mem::size_of::<usize>() + self.0.memory_usage()
+	}
+
+	pub(crate) fn reservation_order(&self) -> ReservationOrder {
+		self.0.reservation_order()
+	}
+
+	/// Reserves every free slot and returns their indices in the
+	/// order they were reserved.
+	pub(crate) fn reserve_all(&self) -> Vec<usize> {
+		let mut indices = vec![];
+		while let Ok(key) = self.0.pop_any_free_slot() {
+			indices.push(key.index);
+		}
+		indices
+	}
+
+	/// Returns the number of free slots that
+	/// [`try_reserve`](Self::try_reserve) can reserve, leaving out
+	/// the ones set aside for priority reservations.
+	pub(crate) fn normal_free_count(&self) -> usize {
+		self.0.normal_free_count()
+	}
+
+	pub(crate) fn is_free(&self, index: usize) -> bool {
+		self.0.is_free(index)
+	}
+
+	pub(crate) fn slot_generation(&self, index: usize) -> usize {
+		self.0.slots[index].generation.load(Ordering::SeqCst)
+	}
+
+	/// Adds `additional` free slots.
+	///
+	/// # Panics
+	///
+	/// Panics if this isn't the only [`Controller`] for the arena.
+	pub(crate) fn grow(&mut self, additional: usize) {
+		Arc::get_mut(&mut self.0)
+			.expect("cannot grow an arena while other controllers for it exist")
+			.grow(additional);
+	}
+
+	pub(crate) fn record_insert(&self) {
+		self.0.inserts_completed.fetch_add(1, Ordering::SeqCst);
+	}
+
+	/// Returns `true` if no other [`Controller`]s share this
+	/// one's state.
+	pub(crate) fn is_unique(&mut self) -> bool {
+		Arc::get_mut(&mut self.0).is_some()
+	}
+
 	pub(crate) fn free(&self, index: usize) {
 		self.0.free(index);
 	}
+
+	/// Returns a reserved slot to the free list without changing
+	/// its generation.
+	pub(crate) fn release(&self, index: usize) {
+		self.0.push_free_slot(index);
+	}
+
+	pub(crate) fn free_many(&self, indices: &[usize]) {
+		self.0.free_many(indices);
+	}
+
+	pub(crate) fn compact_free_list(&self) {
+		self.0.compact_free_list();
+	}
+
+	/// Sets the generation of every free slot to `0` and returns
+	/// the indices of those slots.
+	pub(crate) fn reset_free_slot_generations(&self) -> Vec<usize> {
+		self.0.reset_free_slot_generations()
+	}
+
+	/// Bumps the generation of a slot and makes sure it's never
+	/// reserved again.
+	pub(crate) fn retire(&self, index: usize) {
+		self.0.retire(index);
+	}
+
+	pub(crate) fn is_retired(&self, index: usize) -> bool {
+		self.0.is_retired(index)
+	}
+
+	/// Bumps the generation of a slot without freeing it, which is
+	/// equivalent to freeing it and immediately reserving it again.
+	pub(crate) fn bump_generation(&self, index: usize) {
+		self.0.slots[index]
+			.generation
+			.fetch_add(1, Ordering::SeqCst);
+	}
+
+	/// Overwrites the generation of a slot.
+	pub(crate) fn set_slot_generation(&self, index: usize, generation: usize) {
+		self.0.slots[index]
+			.generation
+			.store(generation, Ordering::SeqCst);
+	}
 }
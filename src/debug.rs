@@ -0,0 +1,99 @@
+//! Types for inspecting the internal state of an [`Arena`](super::Arena).
+
+use crate::slot::{ArenaSlot, ArenaSlotState};
+
+/// A read-only view of a single slot in an [`Arena`](super::Arena).
+///
+/// Returned by [`Arena::debug_slot`](super::Arena::debug_slot).
+#[derive(Debug)]
+pub struct SlotDebug<'a, T> {
+	/// The slot, or `None` if it hasn't been initialized yet.
+	slot: Option<&'a ArenaSlot>,
+	/// The current generation of the slot.
+	generation: usize,
+	/// The item in the slot, if there is one.
+	data: Option<&'a T>,
+}
+
+impl<'a, T> SlotDebug<'a, T> {
+	pub(super) fn new(slot: Option<&'a ArenaSlot>, generation: usize, data: Option<&'a T>) -> Self {
+		Self {
+			slot,
+			generation,
+			data,
+		}
+	}
+
+	fn state(&self) -> ArenaSlotState {
+		match self.slot {
+			Some(slot) => slot.state,
+			None => ArenaSlotState::Free,
+		}
+	}
+
+	/// Returns `true` if the slot contains an item.
+	pub fn is_occupied(&self) -> bool {
+		matches!(self.state(), ArenaSlotState::Occupied { .. })
+	}
+
+	/// Returns the current generation of the slot.
+	pub fn generation(&self) -> usize {
+		self.generation
+	}
+
+	/// Returns the item in the slot, if there is one.
+	pub fn data(&self) -> Option<&'a T> {
+		self.data
+	}
+
+	/// Returns the index of the occupied slot before this one
+	/// in iteration order, if there is one.
+	pub fn prev_link(&self) -> Option<usize> {
+		match self.state() {
+			ArenaSlotState::Free => None,
+			ArenaSlotState::Occupied {
+				previous_occupied_slot_index,
+				..
+			} => previous_occupied_slot_index,
+		}
+	}
+
+	/// Returns the index of the occupied slot after this one
+	/// in iteration order, if there is one.
+	pub fn next_link(&self) -> Option<usize> {
+		match self.state() {
+			ArenaSlotState::Free => None,
+			ArenaSlotState::Occupied {
+				next_occupied_slot_index,
+				..
+			} => next_occupied_slot_index,
+		}
+	}
+}
+
+/// Statistics about the generations of the slots in an
+/// [`Arena`](super::Arena).
+///
+/// Returned by [`Arena::generation_stats`](super::Arena::generation_stats).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GenerationStats {
+	/// The lowest generation of any slot.
+	pub min: usize,
+	/// The highest generation of any slot.
+	pub max: usize,
+	/// The average generation of all slots.
+	pub mean: f64,
+}
+
+/// Statistics about how the free slots in an [`Arena`](super::Arena)
+/// are spread out.
+///
+/// Returned by [`Arena::free_fragmentation`](super::Arena::free_fragmentation).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FragmentationStats {
+	/// The number of runs of consecutive free slots.
+	pub free_runs: usize,
+	/// The number of slots in the longest run of consecutive
+	/// free slots.
+	pub largest_free_run: usize,
+}
@@ -37,3 +37,156 @@ impl Display for InsertWithKeyError {
 }
 
 impl Error for InsertWithKeyError {}
+
+/// An error that can occur when getting mutable references
+/// to two items in an [`Arena`](super::Arena) at once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TwoMutError {
+	/// Both [`Key`](super::Key)s refer to the same slot.
+	SameSlot,
+	/// There is no item associated with the first [`Key`](super::Key).
+	FirstDead,
+	/// There is no item associated with the second [`Key`](super::Key).
+	SecondDead,
+}
+
+impl Display for TwoMutError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			TwoMutError::SameSlot => {
+				f.write_str("Cannot get two mutable references to the same slot")
+			}
+			TwoMutError::FirstDead => f.write_str("There is no item associated with the first key"),
+			TwoMutError::SecondDead => {
+				f.write_str("There is no item associated with the second key")
+			}
+		}
+	}
+}
+
+impl Error for TwoMutError {}
+
+/// Returned when there is no item associated with a
+/// [`Key`](super::Key) in an [`Arena`](super::Arena).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ItemNotFound;
+
+impl Display for ItemNotFound {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str("There is no item associated with this key")
+	}
+}
+
+impl Error for ItemNotFound {}
+
+/// Returned when trying to create an [`Arena`](super::Arena) from
+/// [`ArenaParts`](super::parts::ArenaParts) that aren't consistent
+/// with each other, or from items with [`Key`](super::Key)s that
+/// don't fit in it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct InvalidArenaParts;
+
+impl Display for InvalidArenaParts {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str("The free list or occupied list does not match the slots")
+	}
+}
+
+impl Error for InvalidArenaParts {}
+
+/// The reason there is no item associated with a
+/// [`Key`](super::Key) in an [`Arena`](super::Arena).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AccessError {
+	/// The key's slot index is past the end of the arena.
+	OutOfBounds,
+	/// The key is for the slot's current generation, but no item
+	/// has been inserted with it, either because the key is only
+	/// reserved or because it was never reserved.
+	Freed,
+	/// The slot has been reused or freed since the key was valid.
+	StaleGeneration,
+}
+
+impl Display for AccessError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			AccessError::OutOfBounds => {
+				f.write_str("The key's slot index is out of bounds for this arena")
+			}
+			AccessError::Freed => f.write_str("No item has been inserted with this key"),
+			AccessError::StaleGeneration => {
+				f.write_str("The key's slot has been reused or freed since the key was valid")
+			}
+		}
+	}
+}
+
+impl Error for AccessError {}
+
+/// Returned when trying to create an [`Arena`](super::Arena) whose
+/// slots would take up more memory than allowed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CapacityError;
+
+impl Display for CapacityError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str(
+			"Cannot create an arena with this capacity because it would use too much memory",
+		)
+	}
+}
+
+impl Error for CapacityError {}
+
+/// An error that can occur when inserting an item into an
+/// [`Arena`](super::Arena) with
+/// [`insert_at`](super::Arena::insert_at).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum InsertAtError {
+	/// The slot index is past the end of the arena.
+	OutOfBounds,
+	/// The slot's current generation is different from the
+	/// given one.
+	GenerationMismatch,
+	/// The slot already has an item with the given generation.
+	AlreadyOccupied,
+	/// The slot has the given generation, but it hasn't been
+	/// reserved.
+	NotReserved,
+	/// The slot has been retired, so it can never be filled again.
+	Retired,
+}
+
+impl Display for InsertAtError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			InsertAtError::OutOfBounds => {
+				f.write_str("The slot index is out of bounds for this arena")
+			}
+			InsertAtError::GenerationMismatch => {
+				f.write_str("The slot's generation does not match the given generation")
+			}
+			InsertAtError::AlreadyOccupied => {
+				f.write_str("The slot already has an item with the given generation")
+			}
+			InsertAtError::NotReserved => f.write_str("The slot has not been reserved"),
+			InsertAtError::Retired => f.write_str("The slot has been retired"),
+		}
+	}
+}
+
+impl Error for InsertAtError {}
+
+/// Returned when a [`Key`](super::Key)'s slot index or generation
+/// doesn't fit in a [`PackedKey`](super::PackedKey).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeyTooLarge;
+
+impl Display for KeyTooLarge {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str("The key's slot index or generation does not fit in the packed representation")
+	}
+}
+
+impl Error for KeyTooLarge {}
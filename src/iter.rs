@@ -1,11 +1,8 @@
 //! [`Arena`] iterators.
 
-use std::marker::PhantomData;
+use std::{marker::PhantomData, mem::MaybeUninit};
 
-use crate::{
-	slot::{ArenaSlot, ArenaSlotState},
-	Arena, Key,
-};
+use crate::{slot::ArenaSlot, Arena, Key};
 
 /// Iterates over shared references to the items in
 /// the [`Arena`].
@@ -31,23 +28,18 @@ impl<'a, T> Iterator for Iter<'a, T> {
 	fn next(&mut self) -> Option<Self::Item> {
 		if let Some(index) = self.next_occupied_slot_index {
 			let slot = &self.arena.slots[index];
-			if let ArenaSlotState::Occupied {
+			let data = self
+				.arena
+				.slot_data(index)
+				.expect("the iterator should not encounter a free slot");
+			self.next_occupied_slot_index = slot.next_occupied_slot_index();
+			Some((
+				Key {
+					index,
+					generation: slot.generation,
+				},
 				data,
-				next_occupied_slot_index,
-				..
-			} = &slot.state
-			{
-				self.next_occupied_slot_index = *next_occupied_slot_index;
-				Some((
-					Key {
-						index,
-						generation: slot.generation,
-					},
-					data,
-				))
-			} else {
-				panic!("the iterator should not encounter a free slot");
-			}
+			))
 		} else {
 			None
 		}
@@ -60,7 +52,8 @@ impl<'a, T> Iterator for Iter<'a, T> {
 /// The most recently added items will be visited first.
 pub struct IterMut<'a, T> {
 	next_occupied_slot_index: Option<usize>,
-	slots: *mut [ArenaSlot<T>],
+	slots: &'a [ArenaSlot],
+	values: *mut MaybeUninit<T>,
 	marker: PhantomData<&'a mut Arena<T>>,
 }
 
@@ -68,7 +61,8 @@ impl<'a, T> IterMut<'a, T> {
 	pub(super) fn new(arena: &'a mut Arena<T>) -> Self {
 		Self {
 			next_occupied_slot_index: arena.first_occupied_slot_index,
-			slots: arena.slots.as_mut_slice(),
+			slots: &arena.slots,
+			values: arena.values.as_mut_ptr(),
 			marker: PhantomData,
 		}
 	}
@@ -79,35 +73,26 @@ impl<'a, T> Iterator for IterMut<'a, T> {
 
 	fn next(&mut self) -> Option<Self::Item> {
 		if let Some(index) = self.next_occupied_slot_index {
-			let slot = {
-				// as_mut_ptr and get_unchecked_mut on *mut [T] are unstable :(
-				let start_ptr = self.slots.cast::<ArenaSlot<T>>();
-				// SAFETY: This is always in bounds.
-				let slot_ptr = unsafe { start_ptr.add(index) };
-				// SAFETY:
-				// * This relies on the invariant that `next_occupied_slot_index` never repeats. If
-				//   it did repeat, we could create aliasing mutable references here.
-				// * Lifetime is the same that we mutably borrow the Arena for.
-				unsafe { slot_ptr.as_mut::<'a>() }.unwrap()
-			};
-
-			if let ArenaSlotState::Occupied {
+			let slot = &self.slots[index];
+			assert!(
+				slot.is_occupied(),
+				"the iterator should not encounter a free slot"
+			);
+			// SAFETY:
+			// * The index is in bounds, since `values` is as long as `slots`.
+			// * The slot is occupied, so its value is initialized.
+			// * This relies on the invariant that `next_occupied_slot_index` never repeats. If
+			//   it did repeat, we could create aliasing mutable references here.
+			// * Lifetime is the same that we mutably borrow the Arena for.
+			let data = unsafe { (*self.values.add(index)).assume_init_mut() };
+			self.next_occupied_slot_index = slot.next_occupied_slot_index();
+			Some((
+				Key {
+					index,
+					generation: slot.generation,
+				},
 				data,
-				next_occupied_slot_index,
-				..
-			} = &mut slot.state
-			{
-				self.next_occupied_slot_index = *next_occupied_slot_index;
-				Some((
-					Key {
-						index,
-						generation: slot.generation,
-					},
-					data,
-				))
-			} else {
-				panic!("the iterator should not encounter a free slot");
-			}
+			))
 		} else {
 			None
 		}
@@ -137,28 +122,72 @@ impl<T, F: FnMut(&T) -> bool> Iterator for DrainFilter<'_, T, F> {
 
 	fn next(&mut self) -> Option<Self::Item> {
 		while let Some(index) = self.next_occupied_slot_index {
-			let slot = &mut self.arena.slots[index];
-			if let ArenaSlotState::Occupied {
-				data,
-				next_occupied_slot_index,
-				..
-			} = &mut slot.state
-			{
-				self.next_occupied_slot_index = *next_occupied_slot_index;
-				if (self.filter)(data) {
-					let key = Key {
-						index,
-						generation: slot.generation,
-					};
-					return self
-						.arena
-						.remove_from_slot(index)
-						.map(|element| (key, element));
-				}
-			} else {
-				panic!("the iterator should not encounter a free slot");
+			let slot = &self.arena.slots[index];
+			let key = Key {
+				index,
+				generation: slot.generation,
+			};
+			self.next_occupied_slot_index = slot.next_occupied_slot_index();
+			let data = self
+				.arena
+				.slot_data(index)
+				.expect("the iterator should not encounter a free slot");
+			if (self.filter)(data) {
+				return self
+					.arena
+					.remove_from_slot(index)
+					.map(|element| (key, element));
 			}
 		}
 		None
 	}
 }
+
+/// A cursor over the items in an [`Arena`] that can remove
+/// the item it's currently pointing at.
+///
+/// The most recently added items will be visited first.
+pub struct CursorMut<'a, T> {
+	arena: &'a mut Arena<T>,
+	current_slot_index: Option<usize>,
+	next_occupied_slot_index: Option<usize>,
+}
+
+impl<'a, T> CursorMut<'a, T> {
+	pub(super) fn new(arena: &'a mut Arena<T>) -> Self {
+		Self {
+			next_occupied_slot_index: arena.first_occupied_slot_index,
+			current_slot_index: None,
+			arena,
+		}
+	}
+
+	/// Advances the cursor and returns the next item, or `None`
+	/// if there are no more items.
+	#[allow(clippy::should_implement_trait)]
+	pub fn next(&mut self) -> Option<(Key, &mut T)> {
+		let index = self.next_occupied_slot_index?;
+		let slot = &self.arena.slots[index];
+		let key = Key {
+			index,
+			generation: slot.generation,
+		};
+		self.current_slot_index = Some(index);
+		self.next_occupied_slot_index = slot.next_occupied_slot_index();
+		let data = self
+			.arena
+			.slot_data_mut(index)
+			.expect("the cursor should not encounter a free slot");
+		Some((key, data))
+	}
+
+	/// Removes the item most recently returned by
+	/// [`next`](Self::next) and returns it.
+	///
+	/// Returns `None` if [`next`](Self::next) hasn't returned
+	/// an item yet or the item has already been removed.
+	pub fn remove_current(&mut self) -> Option<T> {
+		let index = self.current_slot_index.take()?;
+		self.arena.remove_from_slot(index)
+	}
+}
@@ -11,43 +11,751 @@ item immediately on the current thread.
 
 #![warn(missing_docs)]
 
+pub mod changes;
 mod controller;
+pub mod debug;
 pub mod error;
 pub mod iter;
+pub mod parts;
+mod single_thread;
 mod slot;
+pub mod split;
+pub mod transaction;
 
 #[cfg(test)]
 mod test;
 
-pub use controller::Controller;
+pub use controller::{ContentionStats, Controller, ReserveReport};
+pub use single_thread::SingleThreadArena;
 
-use error::{ArenaFull, InsertWithKeyError};
-use iter::{DrainFilter, Iter, IterMut};
+use std::{
+	borrow::Borrow,
+	collections::{hash_map::Entry, HashMap, HashSet},
+	convert::TryFrom,
+	fmt::{self, Debug, Formatter},
+	hash::{Hash, Hasher},
+	marker::PhantomData,
+	mem::MaybeUninit,
+	ops::ControlFlow,
+	panic::AssertUnwindSafe,
+	sync::mpsc::{self, Receiver, Sender},
+};
+
+use changes::{ChangeLog, ChangeToken, Changes, DirtyGuard};
+use debug::{FragmentationStats, GenerationStats, SlotDebug};
+use error::{
+	AccessError, ArenaFull, CapacityError, InsertAtError, InsertWithKeyError, InvalidArenaParts,
+	ItemNotFound, KeyTooLarge, TwoMutError,
+};
+use iter::{CursorMut, DrainFilter, Iter, IterMut};
+use parts::{ArenaParts, SlotParts};
 use slot::{ArenaSlot, ArenaSlotState};
+use split::{KeysView, ValuesMut};
+use transaction::Transaction;
 
 /// A unique identifier for an item in an [`Arena`].
+///
+/// Generations are stored as `usize`s and wrap around to `0` after
+/// `usize::MAX`, so a [`Key`] that's held onto while its slot is
+/// reused that many times becomes valid again. On 64-bit targets
+/// this can't happen in practice, but on 32-bit targets, such as
+/// `wasm32`, a slot can be reused `2^32` times in a long-running
+/// program.
+///
+/// The slot index and generation are always `usize`s. To store
+/// [`Key`]s more compactly, convert them to [`PackedKey`]s.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Key {
 	index: usize,
 	generation: usize,
 }
 
+impl Key {
+	/// Returns `true` if both [`Key`]s refer to the same slot,
+	/// regardless of their generations.
+	pub fn is_same_slot(&self, other: &Key) -> bool {
+		self.index == other.index
+	}
+}
+
+/// A [`Key`] that can only be used with an [`Arena<T>`].
+///
+/// Using a [`TypedKey`] from an [`Arena`] of one type with an
+/// [`Arena`] of another type is a compile error:
+///
+/// ```compile_fail
+/// use atomic_arena::Arena;
+///
+/// struct Player;
+/// struct Projectile;
+///
+/// let mut players = Arena::new(1);
+/// let projectiles = Arena::<Projectile>::new(1);
+/// let player = players.insert_typed(Player).unwrap();
+/// projectiles.get_typed(player);
+/// ```
+pub struct TypedKey<T> {
+	key: Key,
+	_marker: PhantomData<fn() -> T>,
+}
+
+impl<T> TypedKey<T> {
+	/// Creates a [`TypedKey`] from an untyped [`Key`].
+	///
+	/// The [`Key`] should come from an [`Arena<T>`].
+	pub fn new(key: Key) -> Self {
+		Self {
+			key,
+			_marker: PhantomData,
+		}
+	}
+
+	/// Returns the untyped [`Key`].
+	pub fn key(self) -> Key {
+		self.key
+	}
+}
+
+impl<T> From<TypedKey<T>> for Key {
+	fn from(key: TypedKey<T>) -> Self {
+		key.key
+	}
+}
+
+// these are implemented manually so they don't require
+// `T` to implement them
+
+impl<T> Debug for TypedKey<T> {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		f.debug_tuple("TypedKey").field(&self.key).finish()
+	}
+}
+
+impl<T> Clone for TypedKey<T> {
+	fn clone(&self) -> Self {
+		*self
+	}
+}
+
+impl<T> Copy for TypedKey<T> {}
+
+impl<T> PartialEq for TypedKey<T> {
+	fn eq(&self, other: &Self) -> bool {
+		self.key == other.key
+	}
+}
+
+impl<T> Eq for TypedKey<T> {}
+
+impl<T> Hash for TypedKey<T> {
+	fn hash<H: Hasher>(&self, state: &mut H) {
+		self.key.hash(state);
+	}
+}
+
+/// A [`Key`] packed into a `u64`, with the lowest `SLOT_BITS` bits
+/// used for the slot index and the remaining bits used for the
+/// generation.
+///
+/// `SLOT_BITS` must be between `1` and `63`. The slot index can be
+/// at most [`MAX_SLOT_INDEX`](Self::MAX_SLOT_INDEX) (`2^SLOT_BITS - 1`),
+/// and the generation can be at most
+/// [`MAX_GENERATION`](Self::MAX_GENERATION)
+/// (`2^(64 - SLOT_BITS) - 1`). For example, `PackedKey<40>` allows
+/// slot indices up to about a trillion and generations up to about
+/// 16 million.
+///
+/// Since generations wrap around at `usize::MAX`, a slot that's
+/// reused often enough will eventually produce [`Key`]s that can't
+/// be packed. On 32-bit targets, packed values with a slot index or
+/// generation above `u32::MAX` don't correspond to any [`Key`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PackedKey<const SLOT_BITS: u32> {
+	bits: u64,
+}
+
+impl<const SLOT_BITS: u32> PackedKey<SLOT_BITS> {
+	const VALID_SLOT_BITS: () = assert!(
+		SLOT_BITS > 0 && SLOT_BITS < 64,
+		"SLOT_BITS must be between 1 and 63"
+	);
+
+	/// The number of bits used for the generation.
+	pub const GENERATION_BITS: u32 = 64 - SLOT_BITS;
+
+	/// The largest slot index that can be packed.
+	pub const MAX_SLOT_INDEX: u64 = u64::MAX >> (64 - SLOT_BITS);
+
+	/// The largest generation that can be packed.
+	pub const MAX_GENERATION: u64 = u64::MAX >> SLOT_BITS;
+
+	/// Packs a [`Key`], or returns `None` if its slot index or
+	/// generation doesn't fit in the available bits.
+	pub fn new(key: Key) -> Option<Self> {
+		#[allow(clippy::let_unit_value)]
+		let () = Self::VALID_SLOT_BITS;
+		let index = key.index as u64;
+		let generation = key.generation as u64;
+		if index > Self::MAX_SLOT_INDEX || generation > Self::MAX_GENERATION {
+			return None;
+		}
+		Some(Self {
+			bits: generation << SLOT_BITS | index,
+		})
+	}
+
+	/// Returns the unpacked [`Key`].
+	pub fn key(self) -> Key {
+		Key {
+			index: (self.bits & Self::MAX_SLOT_INDEX) as usize,
+			generation: (self.bits >> SLOT_BITS) as usize,
+		}
+	}
+
+	/// Returns the packed representation.
+	pub fn to_bits(self) -> u64 {
+		self.bits
+	}
+
+	/// Creates a [`PackedKey`] from a value returned by
+	/// [`to_bits`](Self::to_bits). Every `u64` is a valid packed value.
+	pub fn from_bits(bits: u64) -> Self {
+		#[allow(clippy::let_unit_value)]
+		let () = Self::VALID_SLOT_BITS;
+		Self { bits }
+	}
+}
+
+impl<const SLOT_BITS: u32> TryFrom<Key> for PackedKey<SLOT_BITS> {
+	type Error = KeyTooLarge;
+
+	fn try_from(key: Key) -> Result<Self, Self::Error> {
+		Self::new(key).ok_or(KeyTooLarge)
+	}
+}
+
+impl<const SLOT_BITS: u32> From<PackedKey<SLOT_BITS>> for Key {
+	fn from(key: PackedKey<SLOT_BITS>) -> Self {
+		key.key()
+	}
+}
+
+/// The order in which free slots are handed out when reserving
+/// [`Key`]s.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ReservationOrder {
+	/// The most recently freed slot is reserved first.
+	#[default]
+	Lifo,
+	/// The least recently freed slot is reserved first.
+	///
+	/// This spreads removals across more slots, so generations
+	/// increase more slowly, and makes the order of reservations
+	/// easier to predict.
+	Fifo,
+}
+
 /// A container of items that can be accessed via a [`Key`].
-#[derive(Debug)]
 pub struct Arena<T> {
 	controller: Controller,
-	slots: Vec<ArenaSlot<T>>,
+	slots: Vec<ArenaSlot>,
+	/// The items, stored separately from the slot metadata. Each
+	/// value is initialized if and only if its slot is occupied.
+	values: Vec<MaybeUninit<T>>,
 	first_occupied_slot_index: Option<usize>,
+	last_occupied_slot_index: Option<usize>,
+	len: usize,
+	auto_compact_threshold: Option<f32>,
+	/// The number of items removed since auto-compaction last
+	/// checked whether the free list needs to be compacted.
+	removals_since_compaction: usize,
+	/// The dense position that will be given to the next
+	/// inserted item.
+	next_dense_position: usize,
+	changes: ChangeLog,
+	/// The receivers returned by [`subscribe`](Self::subscribe).
+	utilization: UtilizationSubscribers,
+	/// The [`Key`]s of items changed through a [`DirtyGuard`].
+	dirty: HashSet<Key>,
 }
 
 impl<T> Arena<T> {
 	/// Creates a new [`Arena`] with enough space for `capacity`
 	/// number of items.
 	pub fn new(capacity: usize) -> Self {
+		Self::with_reservation_order(capacity, ReservationOrder::default())
+	}
+
+	/// Creates a new [`Arena`] with enough space for `capacity`
+	/// number of items, or returns an error if the slots would
+	/// need more memory than can be allocated.
+	///
+	/// Unlike [`new`](Self::new), this won't abort the process if
+	/// `capacity` is too large.
+	pub fn try_new(capacity: usize) -> Result<Self, CapacityError> {
+		Self::try_new_with_memory_limit(capacity, isize::MAX as usize)
+	}
+
+	/// Creates a new [`Arena`] with enough space for `capacity`
+	/// number of items, or returns an error if the slots would need
+	/// more than `max_bytes` bytes of memory or more memory than can
+	/// be allocated.
+	///
+	/// This is useful for creating [`Arena`]s with capacities that
+	/// come from untrusted sources.
+	pub fn try_new_with_memory_limit(
+		capacity: usize,
+		max_bytes: usize,
+	) -> Result<Self, CapacityError> {
+		let bytes_per_slot = std::mem::size_of::<ArenaSlot>()
+			+ std::mem::size_of::<T>()
+			+ Controller::memory_per_slot();
+		let bytes = capacity.checked_mul(bytes_per_slot).ok_or(CapacityError)?;
+		if bytes > max_bytes.min(isize::MAX as usize) {
+			return Err(CapacityError);
+		}
+		let controller = Controller::try_new(capacity)?;
+		let mut slots = Vec::new();
+		slots
+			.try_reserve_exact(capacity)
+			.map_err(|_| CapacityError)?;
+		slots.resize_with(capacity, ArenaSlot::new);
+		let mut values = Vec::new();
+		values
+			.try_reserve_exact(capacity)
+			.map_err(|_| CapacityError)?;
+		values.resize_with(capacity, MaybeUninit::uninit);
+		Ok(Self {
+			controller,
+			slots,
+			values,
+			first_occupied_slot_index: None,
+			last_occupied_slot_index: None,
+			len: 0,
+			auto_compact_threshold: None,
+			removals_since_compaction: 0,
+			next_dense_position: 0,
+			changes: ChangeLog::default(),
+			utilization: UtilizationSubscribers::default(),
+			dirty: HashSet::new(),
+		})
+	}
+
+	/// Creates a new [`Arena`] with enough space for `capacity`
+	/// number of items that reserves free slots in the given order.
+	pub fn with_reservation_order(capacity: usize, reservation_order: ReservationOrder) -> Self {
+		Self {
+			controller: Controller::new(capacity, reservation_order),
+			slots: (0..capacity).map(|_| ArenaSlot::new()).collect(),
+			values: (0..capacity).map(|_| MaybeUninit::uninit()).collect(),
+			first_occupied_slot_index: None,
+			last_occupied_slot_index: None,
+			len: 0,
+			auto_compact_threshold: None,
+			removals_since_compaction: 0,
+			next_dense_position: 0,
+			changes: ChangeLog::default(),
+			utilization: UtilizationSubscribers::default(),
+			dirty: HashSet::new(),
+		}
+	}
+
+	/// Creates a new [`Arena`] with enough space for `capacity`
+	/// number of items whose slots are initialized the first
+	/// time they're used.
+	///
+	/// The memory for all of the slots is still allocated up front,
+	/// but it isn't written to until it's needed, which makes
+	/// creating very large, sparsely used [`Arena`]s cheaper.
+	pub fn new_lazy(capacity: usize) -> Self {
+		Self {
+			controller: Controller::new_lazy(capacity),
+			slots: Vec::with_capacity(capacity),
+			values: Vec::with_capacity(capacity),
+			first_occupied_slot_index: None,
+			last_occupied_slot_index: None,
+			len: 0,
+			auto_compact_threshold: None,
+			removals_since_compaction: 0,
+			next_dense_position: 0,
+			changes: ChangeLog::default(),
+			utilization: UtilizationSubscribers::default(),
+			dirty: HashSet::new(),
+		}
+	}
+
+	/// Creates a new [`Arena`] with enough space for `capacity`
+	/// number of items, where `priority_capacity` of the slots can
+	/// only be reserved with [`Controller::try_reserve_priority`].
+	///
+	/// This makes sure priority reservations can still succeed
+	/// after normal reservations have filled up the rest of the
+	/// [`Arena`]. Slots added by [`grow`](Self::grow) can be
+	/// reserved normally.
+	///
+	/// # Panics
+	///
+	/// Panics if `priority_capacity` is greater than `capacity`.
+	pub fn new_with_reserved_lane(capacity: usize, priority_capacity: usize) -> Self {
+		assert!(
+			priority_capacity <= capacity,
+			"the priority capacity cannot be greater than the total capacity"
+		);
 		Self {
-			controller: Controller::new(capacity),
+			controller: Controller::with_priority_lane(capacity, priority_capacity),
 			slots: (0..capacity).map(|_| ArenaSlot::new()).collect(),
+			values: (0..capacity).map(|_| MaybeUninit::uninit()).collect(),
+			first_occupied_slot_index: None,
+			last_occupied_slot_index: None,
+			len: 0,
+			auto_compact_threshold: None,
+			removals_since_compaction: 0,
+			next_dense_position: 0,
+			changes: ChangeLog::default(),
+			utilization: UtilizationSubscribers::default(),
+			dirty: HashSet::new(),
+		}
+	}
+
+	/// Creates a new [`Arena`] with enough space for `capacity`
+	/// number of items where each slot starts at the generation
+	/// returned by `seed(slot_index)` instead of `0`.
+	///
+	/// This is mainly useful for testing how code handles stale
+	/// [`Key`]s from slots with high generations.
+	pub fn new_with_generation_seed(capacity: usize, seed: impl Fn(usize) -> usize) -> Self {
+		let generations: Vec<usize> = (0..capacity).map(seed).collect();
+		let free_list: Vec<usize> = (0..capacity).collect();
+		Self {
+			controller: Controller::from_parts(
+				&generations,
+				&free_list,
+				ReservationOrder::default(),
+			),
+			slots: generations
+				.iter()
+				.map(|generation| ArenaSlot {
+					state: ArenaSlotState::Free,
+					generation: *generation,
+					dense_position: 0,
+					inserted_version: 0,
+				})
+				.collect(),
+			values: (0..capacity).map(|_| MaybeUninit::uninit()).collect(),
+			first_occupied_slot_index: None,
+			last_occupied_slot_index: None,
+			len: 0,
+			auto_compact_threshold: None,
+			removals_since_compaction: 0,
+			next_dense_position: 0,
+			changes: ChangeLog::default(),
+			utilization: UtilizationSubscribers::default(),
+			dirty: HashSet::new(),
+		}
+	}
+
+	/// Creates a new [`Arena`] with every slot filled with a clone
+	/// of `value`.
+	pub fn filled(capacity: usize, value: T) -> Self
+	where
+		T: Clone,
+	{
+		Self::filled_with(capacity, || value.clone())
+	}
+
+	/// Creates a new [`Arena`] with every slot filled with the
+	/// result of calling `f`.
+	///
+	/// The items are inserted in slot order, so the item in the
+	/// last slot will be visited first by iterators.
+	pub fn filled_with(capacity: usize, mut f: impl FnMut() -> T) -> Self {
+		// collect the items first so they're dropped properly
+		// if `f` panics
+		let values: Vec<T> = (0..capacity).map(|_| f()).collect();
+		Self {
+			controller: Controller::new_full(capacity),
+			slots: (0..capacity)
+				.map(|index| ArenaSlot {
+					state: ArenaSlotState::Occupied {
+						previous_occupied_slot_index: if index + 1 < capacity {
+							Some(index + 1)
+						} else {
+							None
+						},
+						next_occupied_slot_index: index.checked_sub(1),
+					},
+					generation: 0,
+					dense_position: index,
+					inserted_version: 0,
+				})
+				.collect(),
+			values: values.into_iter().map(MaybeUninit::new).collect(),
+			first_occupied_slot_index: capacity.checked_sub(1),
+			last_occupied_slot_index: if capacity > 0 { Some(0) } else { None },
+			len: capacity,
+			auto_compact_threshold: None,
+			removals_since_compaction: 0,
+			next_dense_position: capacity,
+			changes: ChangeLog::default(),
+			utilization: UtilizationSubscribers::default(),
+			dirty: HashSet::new(),
+		}
+	}
+
+	/// Creates a new [`Arena`] containing the values of a `HashMap`.
+	///
+	/// Returns the [`Arena`] along with a `HashMap` from each of the
+	/// original keys to the [`Key`] of its value in the [`Arena`].
+	pub fn from_hashmap<K: Eq + Hash>(map: HashMap<K, T>) -> (Self, HashMap<K, Key>) {
+		let mut arena = Self::new(map.len());
+		let keys = map
+			.into_iter()
+			.map(|(map_key, data)| (map_key, arena.insert(data).unwrap()))
+			.collect();
+		(arena, keys)
+	}
+
+	/// Removes the items for which `f(&item)` returns `true` and
+	/// returns a new [`Arena`] with the same capacity containing them.
+	///
+	/// The items keep their [`Key`]s, so each [`Key`] that was valid
+	/// before the split is valid for exactly one of the two [`Arena`]s
+	/// afterward, even once more items are inserted. The two
+	/// [`Arena`]s don't share generations, though, so a [`Key`] for
+	/// an item inserted into one of them later may also be valid
+	/// for an unrelated item in the other.
+	pub fn split_off(&mut self, f: impl FnMut(&T) -> bool) -> Arena<T> {
+		let capacity = self.capacity();
+		let mut slots: Vec<SlotParts<T>> = (0..capacity)
+			.map(|index| SlotParts {
+				generation: self.current_slot_generation(index).wrapping_add(1),
+				data: None,
+			})
+			.collect();
+		let mut occupied_list = vec![];
+		for (key, data) in self.drain_filter(f) {
+			slots[key.index] = SlotParts {
+				generation: key.generation,
+				data: Some(data),
+			};
+			occupied_list.push(key.index);
+		}
+		let free_list = (0..capacity)
+			.filter(|index| slots[*index].data.is_none())
+			.collect();
+		Self::from_parts(ArenaParts {
+			slots,
+			free_list,
+			occupied_list,
+			reservation_order: self.controller.reservation_order(),
+		})
+		.expect("the parts should be consistent")
+	}
+
+	/// Splits the [`Arena`] into two new [`Arena`]s: one containing
+	/// the items for which `f(&item)` returns `true`, and one
+	/// containing the rest.
+	///
+	/// Each new [`Arena`] has exactly enough capacity for its items.
+	/// The items are given new [`Key`]s, so [`Key`]s from the original
+	/// [`Arena`] should not be used with the new ones.
+	pub fn partition(mut self, mut f: impl FnMut(&T) -> bool) -> (Self, Self) {
+		let mut items: Vec<T> = self.drain_filter(|_| true).map(|(_, data)| data).collect();
+		// insert the oldest items first so the iteration order
+		// is preserved
+		items.reverse();
+		let (matching, rest): (Vec<T>, Vec<T>) = items.into_iter().partition(|data| f(data));
+		let from_items = |items: Vec<T>| {
+			let mut arena = Self::new(items.len());
+			for data in items {
+				arena.insert(data).unwrap();
+			}
+			arena
+		};
+		(from_items(matching), from_items(rest))
+	}
+
+	/// Consumes the [`Arena`] and returns its raw components.
+	///
+	/// Any [`Controller`]s for this [`Arena`] that are still alive
+	/// won't be able to reserve [`Key`]s afterward.
+	pub fn into_parts(mut self) -> ArenaParts<T> {
+		let free_list = self.controller.reserve_all();
+		let reservation_order = self.controller.reservation_order();
+		let occupied_list = self.iter().map(|(key, _)| key.index).collect();
+		let slots = (0..self.capacity())
+			.map(|index| SlotParts {
+				generation: self.current_slot_generation(index),
+				data: self.take_slot_data(index),
+			})
+			.collect();
+		ArenaParts {
+			slots,
+			free_list,
+			occupied_list,
+			reservation_order,
+		}
+	}
+
+	/// Creates an [`Arena`] from its raw components.
+	///
+	/// Every [`Key`] that was valid for the [`Arena`] the parts came
+	/// from is valid for the new [`Arena`]. Returns an error if any
+	/// slot is in the free list more than once, any occupied slot is
+	/// in the free list, or the occupied list doesn't contain each
+	/// occupied slot exactly once.
+	pub fn from_parts(parts: ArenaParts<T>) -> Result<Self, InvalidArenaParts> {
+		let ArenaParts {
+			slots,
+			free_list,
+			occupied_list,
+			reservation_order,
+		} = parts;
+		let capacity = slots.len();
+		// every listed slot should be in bounds and listed once
+		let mut listed = vec![false; capacity];
+		for index in free_list.iter().chain(&occupied_list) {
+			if *index >= capacity || listed[*index] {
+				return Err(InvalidArenaParts);
+			}
+			listed[*index] = true;
+		}
+		// the occupied list should contain exactly the occupied slots
+		let occupied_slot_count = slots.iter().filter(|slot| slot.data.is_some()).count();
+		if occupied_list.len() != occupied_slot_count
+			|| occupied_list
+				.iter()
+				.any(|index| slots[*index].data.is_none())
+		{
+			return Err(InvalidArenaParts);
+		}
+		let generations: Vec<usize> = slots.iter().map(|slot| slot.generation).collect();
+		let mut arena_slots = Vec::with_capacity(capacity);
+		let mut values = Vec::with_capacity(capacity);
+		let mut previous_occupied_slot_indices = vec![None; capacity];
+		let mut next_occupied_slot_indices = vec![None; capacity];
+		for pair in occupied_list.windows(2) {
+			next_occupied_slot_indices[pair[0]] = Some(pair[1]);
+			previous_occupied_slot_indices[pair[1]] = Some(pair[0]);
+		}
+		// the occupied list is newest first, so the oldest item
+		// gets the lowest dense position
+		let mut dense_positions = vec![0; capacity];
+		for (dense_position, index) in occupied_list.iter().rev().enumerate() {
+			dense_positions[*index] = dense_position;
+		}
+		for (index, slot) in slots.into_iter().enumerate() {
+			let state = match slot.data {
+				Some(data) => {
+					values.push(MaybeUninit::new(data));
+					ArenaSlotState::Occupied {
+						previous_occupied_slot_index: previous_occupied_slot_indices[index],
+						next_occupied_slot_index: next_occupied_slot_indices[index],
+					}
+				}
+				None => {
+					values.push(MaybeUninit::uninit());
+					ArenaSlotState::Free
+				}
+			};
+			arena_slots.push(ArenaSlot {
+				state,
+				generation: slot.generation,
+				dense_position: dense_positions[index],
+				inserted_version: 0,
+			});
+		}
+		Ok(Self {
+			controller: Controller::from_parts(&generations, &free_list, reservation_order),
+			slots: arena_slots,
+			values,
+			first_occupied_slot_index: occupied_list.first().copied(),
+			last_occupied_slot_index: occupied_list.last().copied(),
+			len: occupied_list.len(),
+			auto_compact_threshold: None,
+			removals_since_compaction: 0,
+			next_dense_position: occupied_list.len(),
+			changes: ChangeLog::default(),
+			utilization: UtilizationSubscribers::default(),
+			dirty: HashSet::new(),
+		})
+	}
+
+	/// Creates an [`Arena`] with enough space for `capacity` number
+	/// of items and puts each item in the slot its [`Key`] refers to,
+	/// so that the [`Key`]s are valid for the new [`Arena`].
+	///
+	/// This is the counterpart to
+	/// [`into_indexed_vec`](Self::into_indexed_vec). The items are
+	/// treated as if they were inserted in the given order. The free
+	/// slots start at generation `0`, so stale [`Key`]s for them may
+	/// become valid again. Returns an error if two items have the
+	/// same slot index or a slot index is out of bounds.
+	pub fn from_indexed(
+		capacity: usize,
+		items: impl IntoIterator<Item = (Key, T)>,
+	) -> Result<Self, InvalidArenaParts> {
+		let mut slots: Vec<SlotParts<T>> = (0..capacity)
+			.map(|_| SlotParts {
+				generation: 0,
+				data: None,
+			})
+			.collect();
+		let mut occupied_list = vec![];
+		for (key, data) in items {
+			let slot = slots.get_mut(key.index).ok_or(InvalidArenaParts)?;
+			if slot.data.is_some() {
+				return Err(InvalidArenaParts);
+			}
+			slot.generation = key.generation;
+			slot.data = Some(data);
+			occupied_list.push(key.index);
+		}
+		// the most recently inserted item comes first
+		occupied_list.reverse();
+		let free_list = (0..capacity)
+			.filter(|index| slots[*index].data.is_none())
+			.collect();
+		Self::from_parts(ArenaParts {
+			slots,
+			free_list,
+			occupied_list,
+			reservation_order: ReservationOrder::default(),
+		})
+	}
+
+	/// Creates a new, empty [`Arena`] with the same capacity that
+	/// shares this [`Arena`]'s [`Controller`], so a [`Key`] reserved
+	/// with the [`Controller`] can be inserted into either [`Arena`].
+	///
+	/// The [`Arena`]s share one set of slots, so they have to be
+	/// used carefully:
+	/// - Each reserved [`Key`] should be inserted into only one of
+	///   the [`Arena`]s. Inserting it into both and then removing it
+	///   from one would free the slot while the other still uses it.
+	/// - Removing an item from either [`Arena`] frees its slot for
+	///   both of them.
+	/// - Slots used by the other [`Arena`] count as reserved, so
+	///   [`used`](Self::used) and [`insert`](Self::insert) take
+	///   both [`Arena`]s into account.
+	/// - Neither [`Arena`] can [`grow`](Self::grow) or
+	///   [`shrink_to_fit`](Self::shrink_to_fit) while the other
+	///   exists.
+	pub fn new_sharing_controller(&self) -> Arena<T> {
+		let capacity = self.capacity();
+		Self {
+			controller: self.controller.clone(),
+			slots: Vec::with_capacity(capacity),
+			values: Vec::with_capacity(capacity),
 			first_occupied_slot_index: None,
+			last_occupied_slot_index: None,
+			len: 0,
+			auto_compact_threshold: None,
+			removals_since_compaction: 0,
+			next_dense_position: 0,
+			changes: ChangeLog::default(),
+			utilization: UtilizationSubscribers::default(),
+			dirty: HashSet::new(),
 		}
 	}
 
@@ -56,17 +764,193 @@ impl<T> Arena<T> {
 		self.controller.clone()
 	}
 
+	/// Returns a reference to the [`Controller`] for this [`Arena`].
+	///
+	/// Unlike [`controller`](Self::controller), this doesn't need to
+	/// clone the [`Controller`], which makes it cheaper to use in
+	/// tight loops.
+	pub fn controller_ref(&self) -> &Controller {
+		&self.controller
+	}
+
 	/// Returns the total capacity for this [`Arena`].
 	pub fn capacity(&self) -> usize {
-		self.slots.len()
+		self.controller.capacity()
+	}
+
+	/// Returns a [`Receiver`] that gets the number of items in the
+	/// [`Arena`] and its capacity, as `(len, capacity)`, every time
+	/// an item is inserted or removed or the capacity changes.
+	///
+	/// Updates are sent for every change, so a [`Receiver`] that's
+	/// only checked occasionally should be drained with
+	/// [`try_iter`](Receiver::try_iter) and the last update used.
+	pub fn subscribe(&mut self) -> Receiver<(usize, usize)> {
+		let (sender, receiver) = mpsc::channel();
+		self.utilization.last_sent = (self.len, self.capacity());
+		self.utilization.senders.push(sender);
+		receiver
+	}
+
+	/// Sends the current length and capacity to the receivers
+	/// returned by [`subscribe`](Self::subscribe) if they've changed
+	/// since the last update, forgetting any receivers that have
+	/// been dropped.
+	fn notify_utilization(&mut self) {
+		if self.utilization.senders.is_empty() || self.utilization.paused {
+			return;
+		}
+		let utilization = (self.len, self.capacity());
+		if utilization == self.utilization.last_sent {
+			return;
+		}
+		self.utilization.last_sent = utilization;
+		self.utilization
+			.senders
+			.retain(|sender| sender.send(utilization).is_ok());
+	}
+
+	/// Stops sending updates to the receivers returned by
+	/// [`subscribe`](Self::subscribe) until
+	/// [`resume_utilization_updates`](Self::resume_utilization_updates)
+	/// is called. Used while an item is taken out of its slot and
+	/// put back, so the temporary change in length isn't reported.
+	fn pause_utilization_updates(&mut self) {
+		self.utilization.paused = true;
+	}
+
+	/// Starts sending updates again after
+	/// [`pause_utilization_updates`](Self::pause_utilization_updates),
+	/// sending one if the length or capacity changed in between.
+	fn resume_utilization_updates(&mut self) {
+		self.utilization.paused = false;
+		self.notify_utilization();
+	}
+
+	/// Increases the capacity of the [`Arena`] by `additional`.
+	///
+	/// # Panics
+	///
+	/// Panics if any [`Controller`]s for this [`Arena`] (returned by
+	/// [`controller`](Self::controller)) are still alive, since they
+	/// wouldn't know about the new slots.
+	pub fn grow(&mut self, additional: usize) {
+		self.controller.grow(additional);
+		self.slots.reserve_exact(additional);
+		self.values.reserve_exact(additional);
+		self.notify_utilization();
+	}
+
+	/// Moves the items into the lowest slots and reduces the capacity
+	/// of the [`Arena`] to the number of items, returning a map from
+	/// each item's old [`Key`] to its new one.
+	///
+	/// The items keep their relative slot order, so items that are
+	/// already packed into the lowest slots keep their [`Key`]s.
+	/// Other old [`Key`]s should not be used with the [`Arena`]
+	/// afterward. Any reserved [`Key`]s are lost.
+	///
+	/// Afterward, [`iter`](Self::iter) visits the items in ascending
+	/// slot order, so two [`Arena`]s with the same items in the same
+	/// relative slot order iterate the same way no matter how they got
+	/// there. Items inserted later are visited first, as usual.
+	///
+	/// # Panics
+	///
+	/// Panics if any [`Controller`]s for this [`Arena`] (returned by
+	/// [`controller`](Self::controller)) are still alive, since they
+	/// would still hand out [`Key`]s for the removed slots.
+	pub fn shrink_to_fit(&mut self) -> HashMap<Key, Key> {
+		assert!(
+			self.controller.is_unique(),
+			"cannot shrink an arena while other controllers for it exist"
+		);
+		let old_keys: Vec<Key> = self.iter_ordered().map(|(key, _)| key).collect();
+		let mut remapped = HashMap::with_capacity(old_keys.len());
+		let mut slots = Vec::with_capacity(old_keys.len());
+		for (index, old_key) in old_keys.into_iter().enumerate() {
+			// items that move get a generation past any old key for
+			// their new slot, so those keys don't become valid again
+			let generation = if old_key.index == index {
+				old_key.generation
+			} else {
+				self.current_slot_generation(index).wrapping_add(1)
+			};
+			slots.push(SlotParts {
+				generation,
+				data: self.take_slot_data(old_key.index),
+			});
+			remapped.insert(old_key, Key { index, generation });
+		}
+		let auto_compact_threshold = self.auto_compact_threshold;
+		let utilization = std::mem::take(&mut self.utilization);
+		let dirty = std::mem::take(&mut self.dirty);
+		*self = Self::from_parts(ArenaParts {
+			occupied_list: (0..slots.len()).collect(),
+			slots,
+			free_list: vec![],
+			reservation_order: self.controller.reservation_order(),
+		})
+		.expect("the parts should be consistent");
+		self.auto_compact_threshold = auto_compact_threshold;
+		self.utilization = utilization;
+		self.dirty = dirty
+			.iter()
+			.filter_map(|key| remapped.get(key).copied())
+			.collect();
+		self.notify_utilization();
+		remapped
+	}
+
+	/// Reserves a [`Key`], growing the [`Arena`] by `grow_by` slots
+	/// first if it's full.
+	///
+	/// # Panics
+	///
+	/// Panics if the [`Arena`] needs to grow and any [`Controller`]s
+	/// for it are still alive, or if `grow_by` is `0` and the
+	/// [`Arena`] is full.
+	pub fn reserve_or_grow(&mut self, grow_by: usize) -> Key {
+		if let Ok(key) = self.controller.try_reserve() {
+			return key;
+		}
+		self.grow(grow_by);
+		self.controller
+			.try_reserve()
+			.expect("cannot reserve a key after growing by 0 slots")
+	}
+
+	/// Returns the number of bytes allocated for the [`Arena`]'s slots
+	/// and items.
+	///
+	/// This doesn't include the memory used by the [`Arena`]'s
+	/// [`Controller`], which is reported by
+	/// [`controller_memory`](Self::controller_memory). Nor does it
+	/// include any heap memory owned by the items themselves.
+	pub fn memory_usage(&self) -> usize {
+		self.slots.capacity() * std::mem::size_of::<ArenaSlot>()
+			+ self.values.capacity() * std::mem::size_of::<T>()
+	}
+
+	/// Returns the number of bytes allocated for the state shared
+	/// by the [`Arena`]'s [`Controller`]s.
+	pub fn controller_memory(&self) -> usize {
+		self.controller.memory_usage()
 	}
 
 	/// Returns the number of items currently in the [`Arena`].
 	pub fn len(&self) -> usize {
-		self.slots
-			.iter()
-			.filter(|slot| matches!(&slot.state, ArenaSlotState::Occupied { .. }))
-			.count()
+		self.len
+	}
+
+	/// Returns the number of slots that can't be reserved right
+	/// now, either because they're occupied or because they've been
+	/// reserved but no item has been inserted into them yet.
+	///
+	/// Unlike [`len`](Self::len), this includes reserved slots, as
+	/// well as retired slots.
+	pub fn used(&self) -> usize {
+		self.capacity() - self.controller.free_count()
 	}
 
 	/// Returns `true` if the [`Arena`] is currently empty.
@@ -74,15 +958,75 @@ impl<T> Arena<T> {
 		self.len() == 0
 	}
 
+	/// Returns the index of the highest occupied slot, or `None`
+	/// if the [`Arena`] is empty.
+	///
+	/// Shrinking the [`Arena`] to a capacity of less than this
+	/// index plus one would remove items.
+	pub fn max_occupied_slot(&self) -> Option<usize> {
+		self.slots.iter().rposition(ArenaSlot::is_occupied)
+	}
+
+	/// Returns the items in the [`Arena`] as a slice ordered by slot
+	/// index if they're packed into the first [`len`](Self::len)
+	/// slots, or `None` if there are any free slots in between.
+	///
+	/// This is the case for an [`Arena`] created with
+	/// [`filled`](Self::filled), or one that items have only been
+	/// inserted into with the default [`ReservationOrder`].
+	pub fn as_contiguous_slice(&self) -> Option<&[T]> {
+		if self.max_occupied_slot().map_or(0, |index| index + 1) != self.len() {
+			return None;
+		}
+		// SAFETY: the highest occupied slot is at index `len - 1`,
+		// and there are `len` occupied slots, so every slot before it
+		// is occupied and has an initialized value. `MaybeUninit<T>`
+		// has the same layout as `T`.
+		Some(unsafe { std::slice::from_raw_parts(self.values.as_ptr().cast::<T>(), self.len()) })
+	}
+
+	/// Returns the fraction of the [`Arena`]'s capacity that is
+	/// currently occupied, from `0.0` to `1.0`.
+	///
+	/// Returns `0.0` if the [`Arena`] has a capacity of `0`.
+	pub fn load_factor(&self) -> f32 {
+		if self.capacity() == 0 {
+			return 0.0;
+		}
+		self.len() as f32 / self.capacity() as f32
+	}
+
+	/// Returns `true` if the [`Key`] has been reserved, but no
+	/// item has been inserted with it yet.
+	pub fn is_reserved(&self, key: Key) -> bool {
+		if key.index >= self.capacity() {
+			return false;
+		}
+		let slot_is_free = match self.slots.get(key.index) {
+			Some(slot) => !slot.is_occupied(),
+			None => true,
+		};
+		// free slots get their generation from the controller, since
+		// an arena sharing the controller may have changed it
+		slot_is_free
+			&& self.controller.slot_generation(key.index) == key.generation
+			&& !self.controller.is_free(key.index)
+			&& !self.controller.is_retired(key.index)
+	}
+
 	/// Tries to insert an item into the [`Arena`] with a previously
 	/// reserved [`Key`].
 	pub fn insert_with_key(&mut self, key: Key, data: T) -> Result<(), InsertWithKeyError> {
 		// make sure the key is valid and reserved
+		if key.index < self.capacity() {
+			self.initialize_slots_up_to(key.index);
+			self.sync_free_slot_generation(key.index);
+		}
 		if let Some(slot) = self.slots.get(key.index) {
 			if slot.generation != key.generation {
 				return Err(InsertWithKeyError::InvalidKey);
 			}
-			if let ArenaSlotState::Occupied { .. } = &slot.state {
+			if slot.is_occupied() || self.controller.is_retired(key.index) {
 				return Err(InsertWithKeyError::KeyNotReserved);
 			}
 		} else {
@@ -96,18 +1040,104 @@ impl<T> Arena<T> {
 		}
 
 		// insert the new data
+		self.values[key.index] = MaybeUninit::new(data);
 		self.slots[key.index].state = ArenaSlotState::Occupied {
-			data,
 			previous_occupied_slot_index: None,
 			next_occupied_slot_index: self.first_occupied_slot_index,
 		};
+		self.slots[key.index].dense_position = self.next_dense_position;
+		self.next_dense_position = self.next_dense_position.wrapping_add(1);
+		self.slots[key.index].inserted_version = self.changes.version;
+		self.controller.record_insert();
 
-		// update the head
+		// update the head, and the tail if this is the only
+		// occupied slot
 		self.first_occupied_slot_index = Some(key.index);
+		if self.last_occupied_slot_index.is_none() {
+			self.last_occupied_slot_index = Some(key.index);
+		}
+		self.len += 1;
+		self.notify_utilization();
 
+		self.check_invariants();
 		Ok(())
 	}
 
+	/// Inserts an item into the slot with the given index if the slot
+	/// is reserved and its current generation is `generation`, and
+	/// returns the [`Key`] for the item.
+	///
+	/// This is useful when several [`Arena`]s are kept in sync and
+	/// agree on slot indices and generations ahead of time. Inserting
+	/// the same slot and generation twice returns
+	/// [`InsertAtError::AlreadyOccupied`] the second time and leaves
+	/// the first item in place.
+	pub fn insert_at(
+		&mut self,
+		slot_index: usize,
+		generation: usize,
+		data: T,
+	) -> Result<Key, InsertAtError> {
+		if slot_index >= self.capacity() {
+			return Err(InsertAtError::OutOfBounds);
+		}
+		self.initialize_slots_up_to(slot_index);
+		self.sync_free_slot_generation(slot_index);
+		let slot = &self.slots[slot_index];
+		if slot.generation != generation {
+			return Err(InsertAtError::GenerationMismatch);
+		}
+		if slot.is_occupied() {
+			return Err(InsertAtError::AlreadyOccupied);
+		}
+		if self.controller.is_retired(slot_index) {
+			return Err(InsertAtError::Retired);
+		}
+		if self.controller.is_free(slot_index) {
+			return Err(InsertAtError::NotReserved);
+		}
+		let key = Key {
+			index: slot_index,
+			generation,
+		};
+		self.insert_with_key(key, data)
+			.expect("the key was checked to be reserved");
+		Ok(key)
+	}
+
+	/// Tries to reserve a [`Key`], and, if successful, returns it
+	/// along with a function that inserts an item into an [`Arena`]
+	/// with that [`Key`].
+	///
+	/// The function can be sent to another thread and called later.
+	/// If it's dropped without being called, or inserting the item
+	/// fails, the [`Key`] is freed so its slot can be reserved again.
+	#[allow(clippy::type_complexity)]
+	pub fn reserve_writer(
+		&self,
+	) -> Result<
+		(
+			Key,
+			impl FnOnce(&mut Arena<T>, T) -> Result<(), InsertWithKeyError>,
+		),
+		ArenaFull,
+	> {
+		let key = self.controller.try_reserve()?;
+		let mut guard = ReservationGuard {
+			controller: self.controller(),
+			key,
+			armed: true,
+		};
+		let writer = move |arena: &mut Arena<T>, data: T| {
+			let result = arena.insert_with_key(guard.key, data);
+			if result.is_ok() {
+				guard.armed = false;
+			}
+			result
+		};
+		Ok((key, writer))
+	}
+
 	/// Tries to reserve a [`Key`], and, if successful, inserts
 	/// an item into the [`Arena`] with that [`Key`] and
 	/// returns the [`Key`].
@@ -117,48 +1147,318 @@ impl<T> Arena<T> {
 		Ok(key)
 	}
 
-	fn remove_from_slot(&mut self, index: usize) -> Option<T> {
-		let slot = &mut self.slots[index];
-		let state = std::mem::replace(&mut slot.state, ArenaSlotState::Free);
-		match state {
-			ArenaSlotState::Free => None,
-			ArenaSlotState::Occupied {
-				data,
-				previous_occupied_slot_index,
-				next_occupied_slot_index,
-			} => {
-				slot.generation += 1;
-				self.controller.free(index);
-
-				// update the pointers of the previous and next slots
-				if let Some(previous_index) = previous_occupied_slot_index {
-					self.slots[previous_index]
-						.set_next_occupied_slot_index(next_occupied_slot_index);
-				}
-				if let Some(next_index) = next_occupied_slot_index {
-					self.slots[next_index]
-						.set_previous_occupied_slot_index(previous_occupied_slot_index);
-				}
-
-				// update the head if needed.
-				//
-				// `first_occupied_slot_index` should always be `Some` in this case,
-				// because this branch of the `match` is only taken if the slot is
-				// occupied, and if any slots are occupied, `first_occupied_slot_index`
-				// should be `Some`. if not, there's a major bug that needs addressing.
-				if self.first_occupied_slot_index.unwrap() == index {
-					self.first_occupied_slot_index = next_occupied_slot_index;
+	/// Inserts a clone of each of the given items into the [`Arena`]
+	/// and returns their [`Key`]s in the same order.
+	///
+	/// If there isn't room for every item, returns an error without
+	/// inserting any of them.
+	pub fn extend_from_slice(&mut self, items: &[T]) -> Result<Vec<Key>, ArenaFull>
+	where
+		T: Clone,
+	{
+		if items.len() > self.controller.normal_free_count() {
+			return Err(ArenaFull);
+		}
+		// clone the items first so a panicking clone can't leave
+		// some of them inserted
+		let items = items.to_vec();
+		let mut keys = Vec::with_capacity(items.len());
+		for _ in 0..items.len() {
+			match self.controller.try_reserve() {
+				Ok(key) => keys.push(key),
+				// another controller may have reserved slots since
+				// they were counted
+				Err(error) => {
+					// release them in reverse so the free list is in
+					// the same order as before. nothing was inserted
+					// with these keys, so their generations can stay
+					// the same
+					for key in keys.iter().rev() {
+						self.controller.release(key.index);
+					}
+					return Err(error);
 				}
-
-				Some(data)
 			}
 		}
+		for (key, item) in keys.iter().zip(items) {
+			self.insert_with_key(*key, item).unwrap();
+		}
+		Ok(keys)
 	}
 
-	/// If the [`Arena`] contains an item with the given [`Key`],
-	/// removes it from the [`Arena`] and returns `Some(item)`.
-	/// Otherwise, returns `None`.
-	pub fn remove(&mut self, key: Key) -> Option<T> {
+	/// Like [`insert`](Self::insert), but returns a [`TypedKey`].
+	pub fn insert_typed(&mut self, data: T) -> Result<TypedKey<T>, ArenaFull> {
+		self.insert(data).map(TypedKey::new)
+	}
+
+	/// Returns a [`DirtyGuard`] for the item in the [`Arena`] with
+	/// the given [`Key`] if it exists. Otherwise, returns `None`.
+	///
+	/// When the guard is dropped, the [`Key`] is recorded as dirty
+	/// until the next call to [`take_dirty`](Self::take_dirty).
+	pub fn get_mut_tracked(&mut self, key: Key) -> Option<DirtyGuard<'_, T>> {
+		if !self.contains(key) {
+			return None;
+		}
+		// SAFETY: the key is valid, so the slot is occupied and
+		// its value is initialized
+		let data = unsafe { self.values[key.index].assume_init_mut() };
+		Some(DirtyGuard::new(key, data, &mut self.dirty))
+	}
+
+	/// Returns the [`Key`]s of the items that have been changed
+	/// through a [`DirtyGuard`] since the last call, ordered by
+	/// slot index.
+	///
+	/// Items that have been removed since they were changed are
+	/// left out.
+	pub fn take_dirty(&mut self) -> Vec<Key> {
+		let mut dirty: Vec<Key> = std::mem::take(&mut self.dirty)
+			.into_iter()
+			.filter(|key| self.contains(key))
+			.collect();
+		dirty.sort_unstable_by_key(|key| key.index);
+		dirty
+	}
+
+	/// Like [`contains`](Self::contains), but takes a [`TypedKey`].
+	pub fn contains_typed(&self, key: TypedKey<T>) -> bool {
+		self.contains(key.key)
+	}
+
+	/// Like [`get`](Self::get), but takes a [`TypedKey`].
+	pub fn get_typed(&self, key: TypedKey<T>) -> Option<&T> {
+		self.get(key.key)
+	}
+
+	/// Like [`get_mut`](Self::get_mut), but takes a [`TypedKey`].
+	pub fn get_typed_mut(&mut self, key: TypedKey<T>) -> Option<&mut T> {
+		self.get_mut(key.key)
+	}
+
+	/// Like [`remove`](Self::remove), but takes a [`TypedKey`].
+	pub fn remove_typed(&mut self, key: TypedKey<T>) -> Option<T> {
+		self.remove(key.key)
+	}
+
+	/// Makes sure the slot with the given index, and every slot
+	/// before it, has been initialized.
+	fn initialize_slots_up_to(&mut self, index: usize) {
+		if index >= self.slots.len() {
+			self.slots.resize_with(index + 1, ArenaSlot::new);
+			self.values.resize_with(index + 1, MaybeUninit::uninit);
+		}
+	}
+
+	/// Returns the current generation of the slot with the given
+	/// index.
+	///
+	/// Free slots get their generation from the controller, since
+	/// reservations can be freed without going through the
+	/// [`Arena`], such as when a writer from
+	/// [`reserve_writer`](Self::reserve_writer) is dropped.
+	fn current_slot_generation(&self, index: usize) -> usize {
+		match self.slots.get(index) {
+			Some(slot) if slot.is_occupied() => slot.generation,
+			_ => self.controller.slot_generation(index),
+		}
+	}
+
+	/// If the slot with the given index is free, copies its
+	/// generation from the controller.
+	///
+	/// For an [`Arena`] with its own [`Controller`], the generations
+	/// of free slots always match already. An [`Arena`] created with
+	/// [`new_sharing_controller`](Self::new_sharing_controller) may
+	/// be behind, since the other [`Arena`] bumps the generation
+	/// when it removes an item.
+	fn sync_free_slot_generation(&mut self, index: usize) {
+		let slot = &mut self.slots[index];
+		if !slot.is_occupied() {
+			slot.generation = self.controller.slot_generation(index);
+		}
+	}
+
+	/// Puts an item back into a slot that was taken out with
+	/// [`unlink_slot`](Self::unlink_slot), between the given
+	/// slots in the occupied list.
+	fn relink_slot(
+		&mut self,
+		index: usize,
+		data: T,
+		previous_occupied_slot_index: Option<usize>,
+		next_occupied_slot_index: Option<usize>,
+	) {
+		self.values[index] = MaybeUninit::new(data);
+		self.slots[index].state = ArenaSlotState::Occupied {
+			previous_occupied_slot_index,
+			next_occupied_slot_index,
+		};
+		match previous_occupied_slot_index {
+			Some(previous_index) => {
+				self.slots[previous_index].set_next_occupied_slot_index(Some(index))
+			}
+			None => self.first_occupied_slot_index = Some(index),
+		}
+		match next_occupied_slot_index {
+			Some(next_index) => {
+				self.slots[next_index].set_previous_occupied_slot_index(Some(index))
+			}
+			None => self.last_occupied_slot_index = Some(index),
+		}
+		self.len += 1;
+		self.notify_utilization();
+		self.check_invariants();
+	}
+
+	/// Removes the item in a slot, frees the slot and gives
+	/// auto-compaction a chance to run. Every removal API that frees
+	/// slots one at a time goes through here.
+	fn remove_from_slot(&mut self, index: usize) -> Option<T> {
+		self.log_removal(index);
+		let data = self.unlink_slot(index)?;
+		self.controller.free(index);
+		self.auto_compact(1);
+		Some(data)
+	}
+
+	/// If a [`ChangeToken`] has been created and the slot with the
+	/// given index is occupied, records that its item is about to
+	/// be removed.
+	fn log_removal(&mut self, index: usize) {
+		let slot = &self.slots[index];
+		if slot.is_occupied() {
+			self.log_removal_of(Key {
+				index,
+				generation: slot.generation,
+			});
+		}
+	}
+
+	/// Records that the item with the given [`Key`] was removed if
+	/// a [`ChangeToken`] has been created.
+	fn log_removal_of(&mut self, key: Key) {
+		let inserted_version = self.slots[key.index].inserted_version;
+		self.changes.log_removal(key, inserted_version);
+	}
+
+	/// Takes the item out of a slot and removes the slot from the
+	/// occupied list without returning it to the controller.
+	fn unlink_slot(&mut self, index: usize) -> Option<T> {
+		let slot = &mut self.slots[index];
+		let state = std::mem::replace(&mut slot.state, ArenaSlotState::Free);
+		match state {
+			ArenaSlotState::Free => None,
+			ArenaSlotState::Occupied {
+				previous_occupied_slot_index,
+				next_occupied_slot_index,
+			} => {
+				slot.generation = slot.generation.wrapping_add(1);
+				// SAFETY: the slot was occupied, so its value was
+				// initialized. the slot has just been marked as free,
+				// so the value won't be read again.
+				let data = unsafe { self.values[index].assume_init_read() };
+
+				// update the pointers of the previous and next slots
+				if let Some(previous_index) = previous_occupied_slot_index {
+					self.slots[previous_index]
+						.set_next_occupied_slot_index(next_occupied_slot_index);
+				}
+				if let Some(next_index) = next_occupied_slot_index {
+					self.slots[next_index]
+						.set_previous_occupied_slot_index(previous_occupied_slot_index);
+				}
+
+				// update the head if needed.
+				//
+				// `first_occupied_slot_index` should always be `Some` in this case,
+				// because this branch of the `match` is only taken if the slot is
+				// occupied, and if any slots are occupied, `first_occupied_slot_index`
+				// should be `Some`. if not, there's a major bug that needs addressing.
+				if self.first_occupied_slot_index.unwrap() == index {
+					self.first_occupied_slot_index = next_occupied_slot_index;
+				}
+				// same goes for the tail
+				if self.last_occupied_slot_index.unwrap() == index {
+					self.last_occupied_slot_index = previous_occupied_slot_index;
+				}
+				self.len -= 1;
+				self.notify_utilization();
+
+				self.check_invariants();
+				Some(data)
+			}
+		}
+	}
+
+	/// Returns the item in the slot with the given index, or `None`
+	/// if the slot is free or out of bounds.
+	fn slot_data(&self, index: usize) -> Option<&T> {
+		if self.slots.get(index)?.is_occupied() {
+			// SAFETY: occupied slots always have an initialized value
+			Some(unsafe { self.values[index].assume_init_ref() })
+		} else {
+			None
+		}
+	}
+
+	/// Returns the item in the slot with the given index, or `None`
+	/// if the slot is free or out of bounds.
+	fn slot_data_mut(&mut self, index: usize) -> Option<&mut T> {
+		if self.slots.get(index)?.is_occupied() {
+			// SAFETY: occupied slots always have an initialized value
+			Some(unsafe { self.values[index].assume_init_mut() })
+		} else {
+			None
+		}
+	}
+
+	/// Takes the item out of a slot and marks the slot as free
+	/// without updating the occupied list, the generation, or the
+	/// cached length. Only used when the [`Arena`] is being torn
+	/// down.
+	fn take_slot_data(&mut self, index: usize) -> Option<T> {
+		let slot = self.slots.get_mut(index)?;
+		if !slot.is_occupied() {
+			return None;
+		}
+		slot.state = ArenaSlotState::Free;
+		// SAFETY: the slot was occupied, so its value was
+		// initialized. the slot has just been marked as free,
+		// so the value won't be read again.
+		Some(unsafe { self.values[index].assume_init_read() })
+	}
+
+	/// When running this crate's tests, panics if the cached length
+	/// doesn't match the number of occupied slots or the length of
+	/// the occupied list. Otherwise does nothing, since checking
+	/// takes O(n) time after every mutation.
+	fn check_invariants(&self) {
+		#[cfg(test)]
+		{
+			let occupied_slot_count = self.slots.iter().filter(|slot| slot.is_occupied()).count();
+			debug_assert_eq!(
+				self.len, occupied_slot_count,
+				"the cached length should match the number of occupied slots"
+			);
+			debug_assert_eq!(
+				self.len,
+				self.iter().count(),
+				"the cached length should match the length of the occupied list"
+			);
+		}
+	}
+
+	/// If the [`Arena`] contains an item with the given [`Key`],
+	/// removes it from the [`Arena`] and returns `Some(item)`.
+	/// Otherwise, returns `None`.
+	///
+	/// If auto-compaction is enabled with
+	/// [`set_auto_compact`](Self::set_auto_compact), this may
+	/// compact the free list, so reserving a [`Key`] from another
+	/// thread at the same time may fail even though the [`Arena`]
+	/// isn't full.
+	pub fn remove(&mut self, key: impl Borrow<Key>) -> Option<T> {
 		// TODO: answer the following questions:
 		// - if you reserve a key, then try to remove the key
 		// without having inserted anything, should the slot
@@ -166,91 +1466,1368 @@ impl<T> Arena<T> {
 		// - what should happen if you try to remove a slot
 		// with the wrong generation? currently the answer is
 		// it just returns None like normal
-		let slot = &mut self.slots[key.index];
+		let key = *key.borrow();
+		let slot = self.slots.get(key.index)?;
 		if slot.generation != key.generation {
 			return None;
 		}
 		self.remove_from_slot(key.index)
 	}
 
-	/// Returns a shared reference to the item in the [`Arena`] with
-	/// the given [`Key`] if it exists. Otherwise, returns `None`.
-	pub fn get(&self, key: Key) -> Option<&T> {
+	/// Like [`remove`](Self::remove), but also returns the [`Key`]s
+	/// of the items visited just before and just after the removed
+	/// item by [`iter`](Self::iter), as they were before it was
+	/// removed.
+	///
+	/// This is useful for keeping a structure that mirrors the
+	/// iteration order of the [`Arena`] in sync.
+	pub fn remove_linked(&mut self, key: Key) -> Option<(T, Option<Key>, Option<Key>)> {
+		if !self.contains(key) {
+			return None;
+		}
 		let slot = &self.slots[key.index];
-		if slot.generation != key.generation {
+		let neighbor_key = |index: usize| Key {
+			index,
+			generation: self.slots[index].generation,
+		};
+		let previous_key = slot.previous_occupied_slot_index().map(neighbor_key);
+		let next_key = slot.next_occupied_slot_index().map(neighbor_key);
+		let data = self.remove(key)?;
+		Some((data, previous_key, next_key))
+	}
+
+	/// Moves the item with the given [`Key`] to the free slot with
+	/// the given index and returns its new [`Key`].
+	///
+	/// The old [`Key`] will no longer be valid, and the item will be
+	/// visited first by iterators, as if it was newly inserted.
+	/// Returns `None` and does nothing if the [`Key`] doesn't have
+	/// an associated item or the target slot isn't free.
+	pub fn relocate(&mut self, key: Key, target_slot_index: usize) -> Option<Key> {
+		if !self.contains(key)
+			|| target_slot_index >= self.capacity()
+			|| !self.controller.is_free(target_slot_index)
+		{
 			return None;
 		}
-		match &slot.state {
-			ArenaSlotState::Free => None,
-			ArenaSlotState::Occupied { data, .. } => Some(data),
+		let new_key = self
+			.controller
+			.try_reserve_in_range(target_slot_index..target_slot_index + 1)
+			.ok()?;
+		let dense_position = self.slots[key.index].dense_position;
+		self.pause_utilization_updates();
+		let data = self.remove(key).expect("the key was checked to be valid");
+		self.insert_with_key(new_key, data)
+			.expect("the new key was just reserved");
+		self.resume_utilization_updates();
+		self.slots[new_key.index].dense_position = dense_position;
+		Some(new_key)
+	}
+
+	/// If the slot with the given index is occupied, removes the
+	/// item in it regardless of its generation and returns it.
+	/// Otherwise, returns `None`.
+	///
+	/// Like with [`remove`](Self::remove), any [`Key`]s for the
+	/// slot will no longer be valid.
+	pub fn force_free(&mut self, slot_index: usize) -> Option<T> {
+		if slot_index >= self.slots.len() {
+			return None;
+		}
+		self.remove_from_slot(slot_index)
+	}
+
+	/// If the [`Arena`] contains an item with the given [`Key`],
+	/// removes it and reserves a new [`Key`] for the same slot.
+	/// Otherwise, returns `None`.
+	///
+	/// The slot is never returned to the free list, so the new
+	/// [`Key`] is guaranteed to use the same slot, with a higher
+	/// generation than the old [`Key`].
+	pub fn remove_and_reserve(&mut self, key: Key) -> Option<Key> {
+		if !self.contains(key) {
+			return None;
+		}
+		self.log_removal(key.index);
+		self.unlink_slot(key.index);
+		self.controller.bump_generation(key.index);
+		Some(Key {
+			index: key.index,
+			generation: self.slots[key.index].generation,
+		})
+	}
+
+	/// Reorders the free slots so that the slots with the lowest
+	/// indices will be reserved first.
+	///
+	/// This doesn't move any items, so all [`Key`]s stay valid.
+	/// Free slots are briefly unavailable while they're being
+	/// reordered, so reserving a [`Key`] from another thread at
+	/// the same time may fail even though the [`Arena`] isn't full.
+	pub fn compact_free_list(&mut self) {
+		self.controller.compact_free_list();
+		self.removals_since_compaction = 0;
+	}
+
+	/// Makes [`remove`](Self::remove) automatically call
+	/// [`compact_free_list`](Self::compact_free_list) whenever the
+	/// index of the highest occupied slot (plus one) divided by the
+	/// number of items exceeds `threshold`.
+	///
+	/// Every other method that removes items, such as
+	/// [`retain`](Self::retain), [`drain_filter`](Self::drain_filter)
+	/// and [`truncate_oldest`](Self::truncate_oldest), counts toward
+	/// auto-compaction the same way.
+	///
+	/// Like [`compact_free_list`](Self::compact_free_list), this only
+	/// reorders the free slots. It doesn't move any items. Checking
+	/// the threshold requires scanning the slots, so it's only
+	/// checked once for every `capacity / 2` removals. Most removals
+	/// skip the check, but the ones that do check take time
+	/// proportional to the capacity.
+	///
+	/// Compacting takes every free slot off the free list while
+	/// reordering them, so while a removal is compacting, reserving
+	/// a [`Key`] from another thread may fail even though the
+	/// [`Arena`] isn't full.
+	pub fn set_auto_compact(&mut self, threshold: f32) {
+		self.auto_compact_threshold = Some(threshold);
+	}
+
+	/// Counts `removals` removals and, if enough have happened since
+	/// the last check, compacts the free list if it exceeds the
+	/// auto-compaction threshold.
+	fn auto_compact(&mut self, removals: usize) {
+		let threshold = match self.auto_compact_threshold {
+			Some(threshold) => threshold,
+			None => return,
+		};
+		self.removals_since_compaction += removals;
+		if self.removals_since_compaction < self.capacity() / 2 {
+			return;
+		}
+		self.removals_since_compaction = 0;
+		let len = self.len();
+		if len == 0 {
+			return;
+		}
+		if let Some(max_occupied_slot) = self.max_occupied_slot() {
+			if (max_occupied_slot + 1) as f32 / len as f32 > threshold {
+				self.compact_free_list();
+			}
+		}
+	}
+
+	/// Sets the generation of every free slot back to `0`.
+	///
+	/// Occupied and reserved slots keep their generations. This
+	/// is only safe to do if no [`Key`]s for items that have been
+	/// removed from the [`Arena`] are still in use, since those
+	/// [`Key`]s could become valid again once their slots are reused.
+	pub fn reset_free_slot_generations(&mut self) {
+		for index in self.controller.reset_free_slot_generations() {
+			if let Some(slot) = self.slots.get_mut(index) {
+				slot.generation = 0;
+			}
+		}
+	}
+
+	/// Permanently retires the slot used by the given [`Key`], removing
+	/// and returning the item in it if there is one.
+	///
+	/// A retired slot will never be reserved again, so the capacity
+	/// of the [`Arena`] is effectively reduced by one. This works for
+	/// [`Key`]s that have been reserved but not inserted with yet.
+	/// Returns `None` and does nothing if the [`Key`] is neither
+	/// valid nor reserved.
+	pub fn retire(&mut self, key: Key) -> Option<T> {
+		if self.contains(key) {
+			self.log_removal(key.index);
+			let data = self.unlink_slot(key.index);
+			self.controller.retire(key.index);
+			data
+		} else if self.is_reserved(key) {
+			self.initialize_slots_up_to(key.index);
+			let slot = &mut self.slots[key.index];
+			slot.generation = slot.generation.wrapping_add(1);
+			self.controller.retire(key.index);
+			None
+		} else {
+			None
+		}
+	}
+
+	/// Overwrites the generation of a slot in both the [`Arena`] and
+	/// its [`Controller`]. Used to give restored items their old
+	/// [`Key`]s back, and by tests to put the slot close to wrapping
+	/// around without freeing it `usize::MAX` times.
+	///
+	/// The slot should be free, since any existing [`Key`] for it
+	/// would stop being valid.
+	pub(crate) fn set_slot_generation(&mut self, slot_index: usize, generation: usize) {
+		self.initialize_slots_up_to(slot_index);
+		self.slots[slot_index].generation = generation;
+		self.controller.set_slot_generation(slot_index, generation);
+	}
+
+	/// Returns `true` if the [`Arena`] has an item associated with
+	/// the given [`Key`].
+	pub fn contains(&self, key: impl Borrow<Key>) -> bool {
+		let key = key.borrow();
+		match self.slots.get(key.index) {
+			Some(slot) => slot.generation == key.generation && slot.is_occupied(),
+			None => false,
+		}
+	}
+
+	/// Returns `true` if the slot with the given index has an item,
+	/// no matter its generation. Returns `false` if the index is out
+	/// of bounds.
+	pub fn is_slot_occupied(&self, slot_index: usize) -> bool {
+		self.slots
+			.get(slot_index)
+			.is_some_and(ArenaSlot::is_occupied)
+	}
+
+	/// Returns a shared reference to the item in the [`Arena`] with
+	/// the given [`Key`] if it exists. Otherwise, returns `None`.
+	pub fn get(&self, key: impl Borrow<Key>) -> Option<&T> {
+		let key = key.borrow();
+		self.get_raw(key.index, key.generation)
+	}
+
+	/// Returns a shared reference to the item in the [`Arena`] with
+	/// the given [`Key`] if it exists. Otherwise, returns an error
+	/// explaining why it doesn't.
+	pub fn access(&self, key: Key) -> Result<&T, AccessError> {
+		self.check_access(key)?;
+		Ok(self.get(key).expect("the key was checked to be valid"))
+	}
+
+	/// Returns a mutable reference to the item in the [`Arena`] with
+	/// the given [`Key`] if it exists. Otherwise, returns an error
+	/// explaining why it doesn't.
+	pub fn access_mut(&mut self, key: Key) -> Result<&mut T, AccessError> {
+		self.check_access(key)?;
+		Ok(self.get_mut(key).expect("the key was checked to be valid"))
+	}
+
+	/// Removes the item in the [`Arena`] with the given [`Key`] and
+	/// returns it if it exists. Otherwise, returns an error explaining
+	/// why it doesn't.
+	pub fn try_remove(&mut self, key: Key) -> Result<T, AccessError> {
+		self.check_access(key)?;
+		Ok(self.remove(key).expect("the key was checked to be valid"))
+	}
+
+	fn check_access(&self, key: Key) -> Result<(), AccessError> {
+		if key.index >= self.capacity() {
+			return Err(AccessError::OutOfBounds);
+		}
+		match self.slots.get(key.index) {
+			Some(slot) if slot.generation != key.generation => Err(AccessError::StaleGeneration),
+			Some(slot) if slot.is_occupied() => Ok(()),
+			Some(_) => Err(AccessError::Freed),
+			// uninitialized slots are free and have never been used
+			None if key.generation == 0 => Err(AccessError::Freed),
+			None => Err(AccessError::StaleGeneration),
+		}
+	}
+
+	/// Returns the generation of the slot used by the given [`Key`]
+	/// if the [`Arena`] has an item associated with it. Otherwise,
+	/// returns `None`.
+	///
+	/// If this returns the same value before and after some other
+	/// work, the [`Key`] still refers to the same item.
+	pub fn read_version(&self, key: Key) -> Option<usize> {
+		if self.contains(key) {
+			Some(self.slots[key.index].generation)
+		} else {
+			None
+		}
+	}
+
+	/// Returns the insertion number of the item in the [`Arena`]
+	/// with the given [`Key`] if it exists. Otherwise, returns `None`.
+	///
+	/// Each item inserted into the [`Arena`] gets the next number
+	/// from a counter, starting at `0`. The number doesn't change
+	/// while the item is in the [`Arena`], even if other items are
+	/// removed or the item is moved with [`relocate`](Self::relocate).
+	/// Numbers aren't reused, but arenas rebuilt from their parts
+	/// number their items again starting from the oldest.
+	pub fn dense_position(&self, key: Key) -> Option<usize> {
+		if self.contains(key) {
+			Some(self.slots[key.index].dense_position)
+		} else {
+			None
+		}
+	}
+
+	/// Hints to the CPU that the item used by the given [`Key`] will
+	/// be accessed soon, so it can start loading it into the cache.
+	///
+	/// This does nothing if the [`Key`]'s slot index is out of bounds
+	/// or the target doesn't support prefetching.
+	pub fn prefetch(&self, key: Key) {
+		let value = match self.values.get(key.index) {
+			Some(value) => value,
+			None => return,
+		};
+		#[cfg(target_arch = "x86_64")]
+		{
+			use std::arch::x86_64::{_mm_prefetch, _MM_HINT_T0};
+			// SAFETY: sse is always available on x86_64, and
+			// prefetching never dereferences the pointer.
+			unsafe { _mm_prefetch::<_MM_HINT_T0>(value.as_ptr().cast()) };
+		}
+		#[cfg(not(target_arch = "x86_64"))]
+		let _ = value;
+	}
+
+	/// Returns a shared reference to the item in the slot with the
+	/// given index if the slot is occupied and has the given
+	/// generation. Otherwise, returns `None`.
+	pub fn get_raw(&self, slot_index: usize, generation: usize) -> Option<&T> {
+		let slot = self.slots.get(slot_index)?;
+		if slot.generation != generation {
+			return None;
 		}
+		self.slot_data(slot_index)
 	}
 
 	/// Returns a mutable reference to the item in the [`Arena`] with
 	/// the given [`Key`] if it exists. Otherwise, returns `None`.
-	pub fn get_mut(&mut self, key: Key) -> Option<&mut T> {
-		let slot = &mut self.slots[key.index];
+	pub fn get_mut(&mut self, key: impl Borrow<Key>) -> Option<&mut T> {
+		let key = key.borrow();
+		self.get_mut_with_key(*key).map(|(_, data)| data)
+	}
+
+	/// Returns the [`Key`] of the item in the [`Arena`] with the given
+	/// [`Key`] along with a mutable reference to the item if it exists.
+	/// Otherwise, returns `None`.
+	pub fn get_mut_with_key(&mut self, key: Key) -> Option<(Key, &mut T)> {
+		let slot = self.slots.get(key.index)?;
 		if slot.generation != key.generation {
 			return None;
 		}
-		match &mut slot.state {
-			ArenaSlotState::Free => None,
-			ArenaSlotState::Occupied { data, .. } => Some(data),
+		let key = Key {
+			index: key.index,
+			generation: slot.generation,
+		};
+		self.slot_data_mut(key.index).map(|data| (key, data))
+	}
+
+	/// Replaces the items in the [`Arena`] with the given [`Key`]s
+	/// with the new values. The items keep the same [`Key`]s.
+	///
+	/// Returns the [`Key`]s that don't have an associated item,
+	/// whose new values are dropped, in no particular order.
+	pub fn apply_updates(&mut self, updates: HashMap<Key, T>) -> Vec<Key> {
+		let mut stale_keys = vec![];
+		for (key, data) in updates {
+			match self.get_mut(key) {
+				Some(existing) => *existing = data,
+				None => stale_keys.push(key),
+			}
 		}
+		stale_keys
+	}
+
+	/// Replaces the item in the [`Arena`] with the given [`Key`] with
+	/// the result of calling `f` on it. The item keeps the same [`Key`].
+	///
+	/// If `f` panics, the item is removed from the [`Arena`] as if
+	/// [`remove`](Self::remove) had been called, so the [`Key`] will
+	/// no longer be valid, and the panic is resumed.
+	pub fn update(&mut self, key: Key, f: impl FnOnce(T) -> T) -> Result<(), ItemNotFound> {
+		if !self.contains(key) {
+			return Err(ItemNotFound);
+		}
+		let previous_occupied_slot_index = self.slots[key.index].previous_occupied_slot_index();
+		let next_occupied_slot_index = self.slots[key.index].next_occupied_slot_index();
+		// take the item out of the slot. at this point, the item is
+		// removed from the arena, except for the slot not being
+		// freed in the controller yet
+		self.pause_utilization_updates();
+		let data = self
+			.unlink_slot(key.index)
+			.expect("the slot was checked to be occupied");
+		match std::panic::catch_unwind(AssertUnwindSafe(|| f(data))) {
+			Ok(data) => {
+				// put the new item back in the same slot and at the
+				// same position in the occupied list
+				self.slots[key.index].generation = key.generation;
+				self.relink_slot(
+					key.index,
+					data,
+					previous_occupied_slot_index,
+					next_occupied_slot_index,
+				);
+				self.resume_utilization_updates();
+				Ok(())
+			}
+			Err(payload) => {
+				self.log_removal_of(key);
+				self.controller.free(key.index);
+				self.auto_compact(1);
+				self.resume_utilization_updates();
+				std::panic::resume_unwind(payload);
+			}
+		}
+	}
+
+	/// Returns mutable references to the two items in the [`Arena`]
+	/// with the given [`Key`]s.
+	///
+	/// Returns an error if the [`Key`]s refer to the same slot or
+	/// either of them doesn't have an associated item. Two [`Key`]s
+	/// with the same slot index but different generations count as
+	/// referring to the same slot, even though at most one of them
+	/// can be valid.
+	pub fn get_two_mut(&mut self, a: Key, b: Key) -> Result<(&mut T, &mut T), TwoMutError> {
+		if a.is_same_slot(&b) {
+			return Err(TwoMutError::SameSlot);
+		}
+		if !self.contains(a) {
+			return Err(TwoMutError::FirstDead);
+		}
+		if !self.contains(b) {
+			return Err(TwoMutError::SecondDead);
+		}
+		let (value_a, value_b) = if a.index < b.index {
+			let (left, right) = self.values.split_at_mut(b.index);
+			(&mut left[a.index], &mut right[0])
+		} else {
+			let (left, right) = self.values.split_at_mut(a.index);
+			(&mut right[0], &mut left[b.index])
+		};
+		// SAFETY: both slots were checked to be occupied, so both
+		// values are initialized.
+		Ok(unsafe { (value_a.assume_init_mut(), value_b.assume_init_mut()) })
+	}
+
+	/// Like [`get_two_mut`](Self::get_two_mut), but returns each
+	/// item's [`Key`] along with the mutable reference to it, and
+	/// returns `None` instead of an error.
+	#[allow(clippy::type_complexity)]
+	pub fn get_two_mut_indexed(
+		&mut self,
+		a: Key,
+		b: Key,
+	) -> Option<((Key, &mut T), (Key, &mut T))> {
+		let (data_a, data_b) = self.get_two_mut(a, b).ok()?;
+		Some(((a, data_a), (b, data_b)))
+	}
+
+	/// Swaps the items in the [`Arena`] with the given [`Key`]s, so
+	/// that each [`Key`] refers to the item the other one used to.
+	///
+	/// Both [`Key`]s stay valid and their slots keep their
+	/// positions in the iteration order. There's no way to swap
+	/// which slots the [`Key`]s refer to instead, since a [`Key`]
+	/// always stores the index of its slot.
+	///
+	/// Returns the same errors as [`get_two_mut`](Self::get_two_mut).
+	pub fn swap(&mut self, a: Key, b: Key) -> Result<(), TwoMutError> {
+		let (data_a, data_b) = self.get_two_mut(a, b)?;
+		std::mem::swap(data_a, data_b);
+		Ok(())
+	}
+
+	/// Returns mutable references to the items in the [`Arena`]
+	/// with the given [`Key`]s.
+	///
+	/// Returns `None` if any of the [`Key`]s doesn't have an
+	/// associated item or if any two [`Key`]s refer to the same slot.
+	pub fn get_disjoint_mut<const N: usize>(&mut self, keys: [Key; N]) -> Option<[&mut T; N]> {
+		for (i, key) in keys.iter().enumerate() {
+			if !self.contains(*key) || keys[..i].iter().any(|other| key.is_same_slot(other)) {
+				return None;
+			}
+		}
+		// SAFETY: we just checked that every key is valid and
+		// refers to a distinct slot.
+		Some(unsafe { self.get_disjoint_unchecked_mut(keys) })
+	}
+
+	/// Returns mutable references to the items in the [`Arena`]
+	/// with the given [`Key`]s without checking that they're valid.
+	///
+	/// In debug builds, this will panic if any of the [`Key`]s is
+	/// invalid.
+	///
+	/// # Safety
+	///
+	/// Every [`Key`] must have an associated item in the [`Arena`],
+	/// and no two [`Key`]s may refer to the same slot.
+	pub unsafe fn get_disjoint_unchecked_mut<const N: usize>(
+		&mut self,
+		keys: [Key; N],
+	) -> [&mut T; N] {
+		for (i, key) in keys.iter().enumerate() {
+			debug_assert!(self.contains(*key), "No item associated with this key");
+			debug_assert!(
+				!keys[..i].iter().any(|other| key.is_same_slot(other)),
+				"Multiple keys refer to the same slot"
+			);
+		}
+		let values = self.values.as_mut_ptr();
+		keys.map(|key| {
+			// SAFETY: the caller guarantees that the keys are valid, so
+			// the slot index is in bounds and the value is initialized,
+			// and that they refer to distinct slots, so we never create
+			// aliasing mutable references.
+			unsafe { (*values.add(key.index)).assume_init_mut() }
+		})
+	}
+
+	/// Returns mutable references to the items in the [`Arena`]
+	/// with the given [`Key`]s, or `None` for each [`Key`] that
+	/// doesn't have an associated item.
+	///
+	/// Unlike [`get_disjoint_mut`](Self::get_disjoint_mut), [`Key`]s
+	/// without an associated item are allowed to refer to the same
+	/// slot as other [`Key`]s.
+	///
+	/// # Panics
+	///
+	/// Panics if any two [`Key`]s with associated items refer to
+	/// the same slot.
+	pub fn get_many_mut_tolerant<const N: usize>(&mut self, keys: [Key; N]) -> [Option<&mut T>; N] {
+		let live = self.check_live_keys_disjoint(&keys);
+		let values = self.values.as_mut_ptr();
+		let mut i = 0;
+		keys.map(|key| {
+			let is_live = live[i];
+			i += 1;
+			// SAFETY: live keys are valid, so the slot index is in
+			// bounds and the value is initialized, and we just checked
+			// that they refer to distinct slots, so we never create
+			// aliasing mutable references.
+			is_live.then(|| unsafe { (*values.add(key.index)).assume_init_mut() })
+		})
+	}
+
+	/// Returns whether each of the given [`Key`]s has an associated
+	/// item, panicking if any two that do refer to the same slot.
+	fn check_live_keys_disjoint<const N: usize>(&self, keys: &[Key; N]) -> [bool; N] {
+		let live = keys.map(|key| self.contains(key));
+		for (i, key) in keys.iter().enumerate() {
+			assert!(
+				!live[i]
+					|| !keys[..i]
+						.iter()
+						.zip(&live)
+						.any(|(other, other_live)| *other_live && key.is_same_slot(other)),
+				"Multiple keys refer to the same slot"
+			);
+		}
+		live
+	}
+
+	/// Calls `f` with the position in `keys` of and a mutable
+	/// reference to the item associated with each of the given
+	/// [`Key`]s, in the order the [`Key`]s are given.
+	///
+	/// Unlike [`get_disjoint_mut`](Self::get_disjoint_mut), [`Key`]s
+	/// that don't have an associated item are skipped.
+	///
+	/// # Panics
+	///
+	/// Panics if any two [`Key`]s with associated items refer to
+	/// the same slot.
+	pub fn modify_many<const N: usize>(
+		&mut self,
+		keys: [Key; N],
+		mut f: impl FnMut(usize, &mut T),
+	) {
+		self.check_live_keys_disjoint(&keys);
+		for (i, key) in keys.iter().enumerate() {
+			if let Some(data) = self.get_mut(key) {
+				f(i, data);
+			}
+		}
+	}
+
+	/// Removes the most recently inserted item from the [`Arena`]
+	/// and returns it along with its [`Key`], or returns `None` if
+	/// the [`Arena`] is empty.
+	pub fn remove_newest(&mut self) -> Option<(Key, T)> {
+		let index = self.first_occupied_slot_index?;
+		self.remove_at_slot_index(index)
+	}
+
+	/// Removes the least recently inserted item from the [`Arena`]
+	/// and returns it along with its [`Key`], or returns `None` if
+	/// the [`Arena`] is empty.
+	pub fn remove_oldest(&mut self) -> Option<(Key, T)> {
+		let index = self.last_occupied_slot_index?;
+		self.remove_at_slot_index(index)
+	}
+
+	fn remove_at_slot_index(&mut self, index: usize) -> Option<(Key, T)> {
+		let key = Key {
+			index,
+			generation: self.slots[index].generation,
+		};
+		self.remove(key).map(|data| (key, data))
+	}
+
+	/// Removes the oldest items from the [`Arena`] until it
+	/// contains at most `max_len` items.
+	pub fn truncate_oldest(&mut self, max_len: usize) {
+		let mut len = self.len();
+		while len > max_len {
+			match self.last_occupied_slot_index {
+				Some(index) => self.remove_from_slot(index),
+				None => return,
+			};
+			len -= 1;
+		}
+	}
+
+	/// Rotates the order items are visited in so that the item
+	/// `n` steps from the front (wrapping around) is visited first.
+	///
+	/// The items themselves are not moved, so all [`Key`]s stay
+	/// valid.
+	pub fn rotate_occupied(&mut self, n: usize) {
+		let (first_index, last_index) = match (
+			self.first_occupied_slot_index,
+			self.last_occupied_slot_index,
+		) {
+			(Some(first_index), Some(last_index)) => (first_index, last_index),
+			_ => return,
+		};
+		let n = n % self.len();
+		if n == 0 {
+			return;
+		}
+		let mut new_first_index = first_index;
+		for _ in 0..n {
+			new_first_index = self.slots[new_first_index]
+				.next_occupied_slot_index()
+				.expect("expected the occupied list to have at least n items");
+		}
+		let new_last_index = self.slots[new_first_index]
+			.previous_occupied_slot_index()
+			.expect("expected the new first item to have a previous item");
+		// join the old ends of the list together
+		self.slots[last_index].set_next_occupied_slot_index(Some(first_index));
+		self.slots[first_index].set_previous_occupied_slot_index(Some(last_index));
+		// and split the list at the new ends
+		self.slots[new_first_index].set_previous_occupied_slot_index(None);
+		self.slots[new_last_index].set_next_occupied_slot_index(None);
+		self.first_occupied_slot_index = Some(new_first_index);
+		self.last_occupied_slot_index = Some(new_last_index);
+		self.check_invariants();
 	}
 
 	/// Retains only the elements specified by the predicate.
 	///
 	/// In other words, remove all elements e such that f(&e) returns false.
 	pub fn retain(&mut self, mut f: impl FnMut(&T) -> bool) {
-		let mut index = match self.first_occupied_slot_index {
-			Some(index) => index,
-			None => return,
-		};
-		loop {
-			if let ArenaSlotState::Occupied {
-				data,
-				next_occupied_slot_index,
-				..
-			} = &self.slots[index].state
-			{
-				let next_occupied_slot_index = next_occupied_slot_index.as_ref().copied();
-				if !f(data) {
-					self.remove_from_slot(index);
+		// the removed slots are given back to the controller all at
+		// once at the end, even if `f` or a destructor panics
+		let mut freed = vec![];
+		let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+			let mut index = match self.first_occupied_slot_index {
+				Some(index) => index,
+				None => return,
+			};
+			loop {
+				if let Some(data) = self.slot_data(index) {
+					let next_occupied_slot_index = self.slots[index].next_occupied_slot_index();
+					if !f(data) {
+						self.log_removal(index);
+						let data = self.unlink_slot(index);
+						freed.push(index);
+						drop(data);
+					}
+					index = match next_occupied_slot_index {
+						Some(index) => index,
+						None => return,
+					}
+				} else {
+					panic!("expected the slot pointed to by first_occupied_slot_index/next_occupied_slot_index to be occupied")
+				}
+			}
+		}));
+		self.controller.free_many(&freed);
+		self.auto_compact(freed.len());
+		if let Err(payload) = result {
+			std::panic::resume_unwind(payload);
+		}
+	}
+
+	/// Folds every item in the [`Arena`] and its [`Key`] into a
+	/// single value, in the same order as [`iter`](Self::iter).
+	pub fn fold<B>(&self, init: B, mut f: impl FnMut(B, Key, &T) -> B) -> B {
+		self.iter().fold(init, |acc, (key, data)| f(acc, key, data))
+	}
+
+	/// Returns the [`Key`] of and a reference to the first item in
+	/// the [`Arena`] for which `f(&item)` returns `true`, or `None`
+	/// if there isn't one.
+	///
+	/// The most recently added items are checked first.
+	pub fn find(&self, mut f: impl FnMut(&T) -> bool) -> Option<(Key, &T)> {
+		self.iter().find(|(_, data)| f(data))
+	}
+
+	/// Returns a `Vec` containing clones of the items in the
+	/// [`Arena`], ordered by slot index, along with a `HashMap`
+	/// from each item's [`Key`] to its position in the `Vec`.
+	pub fn to_dense(&self) -> (Vec<T>, HashMap<Key, usize>)
+	where
+		T: Clone,
+	{
+		let mut dense = Vec::with_capacity(self.len());
+		let mut positions = HashMap::with_capacity(self.len());
+		for (key, data) in self.iter_ordered() {
+			positions.insert(key, dense.len());
+			dense.push(data.clone());
+		}
+		(dense, positions)
+	}
+
+	/// Writes clones of items from a `Vec` created by
+	/// [`to_dense`](Self::to_dense) back into the [`Arena`].
+	///
+	/// Each item with a [`Key`] in `positions` is replaced with
+	/// `dense[position]` and keeps the same [`Key`]. [`Key`]s that
+	/// don't have an associated item are ignored.
+	///
+	/// # Panics
+	///
+	/// Panics if a position for a valid [`Key`] is out of bounds
+	/// for `dense`.
+	pub fn from_dense_updates(&mut self, dense: &[T], positions: &HashMap<Key, usize>)
+	where
+		T: Clone,
+	{
+		for (key, position) in positions {
+			if let Some(data) = self.get_mut(key) {
+				data.clone_from(&dense[*position]);
+			}
+		}
+	}
+
+	/// Returns `true` if both [`Arena`]s contain the same items,
+	/// regardless of their [`Key`]s or the order they were inserted in.
+	///
+	/// Each item is matched with at most one item in the other
+	/// [`Arena`], so duplicates have to appear the same number of
+	/// times in both. This compares every pair of items, so it
+	/// takes quadratic time.
+	pub fn eq_unordered(&self, other: &Arena<T>) -> bool
+	where
+		T: PartialEq,
+	{
+		if self.len() != other.len() {
+			return false;
+		}
+		let mut unmatched: Vec<&T> = other.iter().map(|(_, data)| data).collect();
+		for (_, data) in self.iter() {
+			match unmatched.iter().position(|other_data| *other_data == data) {
+				Some(position) => {
+					unmatched.swap_remove(position);
 				}
-				index = match next_occupied_slot_index {
-					Some(index) => index,
-					None => return,
+				None => return false,
+			}
+		}
+		true
+	}
+
+	/// Returns the number of items in the [`Arena`] for which
+	/// `f(&item)` returns `true`.
+	pub fn count(&self, mut f: impl FnMut(&T) -> bool) -> usize {
+		self.iter().filter(|(_, data)| f(data)).count()
+	}
+
+	/// Returns `true` if `f(&item)` returns `true` for any item
+	/// in the [`Arena`].
+	///
+	/// Stops checking items as soon as one matches.
+	pub fn any(&self, mut f: impl FnMut(&T) -> bool) -> bool {
+		self.iter().any(|(_, data)| f(data))
+	}
+
+	/// Returns `true` if `f(&item)` returns `true` for every item
+	/// in the [`Arena`].
+	///
+	/// Stops checking items as soon as one doesn't match.
+	pub fn all(&self, mut f: impl FnMut(&T) -> bool) -> bool {
+		self.iter().all(|(_, data)| f(data))
+	}
+
+	/// Returns the [`Key`] of and a reference to the item that gives
+	/// the minimum value from `f`.
+	///
+	/// If several items are equally minimum, the first one in
+	/// iteration order is returned. Returns `None` if the [`Arena`]
+	/// is empty.
+	pub fn min_by_key<K: Ord>(&self, mut f: impl FnMut(&T) -> K) -> Option<(Key, &T)> {
+		self.iter().min_by_key(|(_, data)| f(data))
+	}
+
+	/// Returns the [`Key`] of and a reference to the item that gives
+	/// the maximum value from `f`.
+	///
+	/// If several items are equally maximum, the last one in
+	/// iteration order is returned, like [`Iterator::max_by_key`]. Returns `None` if the [`Arena`]
+	/// is empty.
+	pub fn max_by_key<K: Ord>(&self, mut f: impl FnMut(&T) -> K) -> Option<(Key, &T)> {
+		self.iter().max_by_key(|(_, data)| f(data))
+	}
+
+	/// Removes all but the first item (in iteration order) that
+	/// resolve to the same key according to the `key` function.
+	///
+	/// Returns a list of `(removed_key, kept_key)` pairs, mapping the
+	/// [`Key`] of each removed item to the [`Key`] of the item it
+	/// was a duplicate of.
+	pub fn dedup_by_key<K: Eq + Hash>(&mut self, mut key: impl FnMut(&T) -> K) -> Vec<(Key, Key)> {
+		let mut first_keys = HashMap::new();
+		let mut duplicates = vec![];
+		for (arena_key, data) in self.iter() {
+			match first_keys.entry(key(data)) {
+				Entry::Occupied(entry) => {
+					duplicates.push((arena_key, *entry.get()));
+				}
+				Entry::Vacant(entry) => {
+					entry.insert(arena_key);
+				}
+			}
+		}
+		for (removed_key, _) in &duplicates {
+			self.remove(*removed_key);
+		}
+		duplicates
+	}
+
+	/// Returns a [`SlotDebug`] for inspecting the slot with the
+	/// given index, or `None` if the index is out of bounds.
+	pub fn debug_slot(&self, slot_index: usize) -> Option<SlotDebug<'_, T>> {
+		if slot_index >= self.capacity() {
+			return None;
+		}
+		Some(SlotDebug::new(
+			self.slots.get(slot_index),
+			self.current_slot_generation(slot_index),
+			self.slot_data(slot_index),
+		))
+	}
+
+	/// Returns `true` if the number of items in the [`Arena`], the
+	/// number of free slots, and the number of reserved slots add up
+	/// to the [`Arena`]'s capacity.
+	///
+	/// Reserved slots are slots that the [`Controller`] has handed
+	/// out but that don't have an item yet, including retired slots.
+	/// This returns `false` if an occupied slot is also marked as
+	/// free, which would mean its [`Key`] could be reserved again.
+	/// Reservations made by other threads at the same time may
+	/// make this return `false` spuriously.
+	pub fn debug_consistency(&self) -> bool {
+		let free_count = self.controller.free_count();
+		let reserved_count = (0..self.capacity())
+			.filter(|index| {
+				let occupied = self.slots.get(*index).is_some_and(ArenaSlot::is_occupied);
+				!occupied && !self.controller.is_free(*index)
+			})
+			.count();
+		self.len() + free_count + reserved_count == self.capacity()
+	}
+
+	/// Panics if any slots have been reserved without an item
+	/// being inserted into them.
+	///
+	/// Those slots can't be used until they're freed, so forgetting
+	/// about a reserved [`Key`] shrinks the usable capacity of the
+	/// [`Arena`]. Retired slots count as reserved, as do slots used
+	/// by an [`Arena`] created with
+	/// [`new_sharing_controller`](Self::new_sharing_controller).
+	pub fn assert_no_reservation_leaks(&self) {
+		let reserved_count = self.used() - self.len();
+		assert!(
+			reserved_count == 0,
+			"{} slot(s) were reserved but never had an item inserted",
+			reserved_count
+		);
+	}
+
+	/// Returns statistics about the generations of all the slots
+	/// in the [`Arena`], both free and occupied.
+	///
+	/// A slot's generation increases every time an item is removed
+	/// from it, so high generations indicate heavily reused slots.
+	/// If the [`Arena`] has a capacity of `0`, all the stats are `0`.
+	pub fn generation_stats(&self) -> GenerationStats {
+		let generations = (0..self.capacity()).map(|index| self.current_slot_generation(index));
+		GenerationStats {
+			min: generations.clone().min().unwrap_or(0),
+			max: generations.clone().max().unwrap_or(0),
+			mean: if self.capacity() == 0 {
+				0.0
+			} else {
+				generations.map(|generation| generation as f64).sum::<f64>()
+					/ self.capacity() as f64
+			},
+		}
+	}
+
+	/// Returns statistics about how the free slots in the [`Arena`]
+	/// are spread out.
+	///
+	/// Many short runs of free slots between occupied slots mean
+	/// the [`Arena`] is fragmented. Reserved slots don't count as
+	/// free.
+	pub fn free_fragmentation(&self) -> FragmentationStats {
+		let mut stats = FragmentationStats {
+			free_runs: 0,
+			largest_free_run: 0,
+		};
+		let mut current_run = 0;
+		for index in 0..self.capacity() {
+			if self.controller.is_free(index) {
+				if current_run == 0 {
+					stats.free_runs += 1;
 				}
+				current_run += 1;
+				stats.largest_free_run = stats.largest_free_run.max(current_run);
 			} else {
-				panic!("expected the slot pointed to by first_occupied_slot_index/next_occupied_slot_index to be occupied")
+				current_run = 0;
 			}
 		}
+		stats
+	}
+
+	/// Returns a [`ChangeToken`] that can be passed to
+	/// [`changes_since`](Self::changes_since) to find out which
+	/// items have been inserted and removed since now.
+	///
+	/// Only the most recent [`ChangeToken`] can be used.
+	pub fn mark(&mut self) -> ChangeToken {
+		let version = self.changes.version;
+		self.changes.version = version.wrapping_add(1);
+		self.changes.marked_version = Some(version);
+		self.changes.removed.clear();
+		ChangeToken { version }
+	}
+
+	/// Returns the [`Changes`] to the [`Arena`] since the given
+	/// [`ChangeToken`] was created by [`mark`](Self::mark).
+	///
+	/// Items that were both inserted and removed since then aren't
+	/// reported. An item that's updated in place isn't reported,
+	/// but one moved with [`relocate`](Self::relocate) is reported
+	/// as removed under its old [`Key`] and inserted under its new
+	/// one.
+	///
+	/// # Panics
+	///
+	/// Panics if the [`ChangeToken`] isn't the most recent one
+	/// created for this [`Arena`].
+	pub fn changes_since(&self, token: ChangeToken) -> Changes {
+		assert!(
+			self.changes.marked_version == Some(token.version),
+			"only the most recent change token can be used"
+		);
+		let inserted = self
+			.slots
+			.iter()
+			.enumerate()
+			.filter(|(_, slot)| slot.is_occupied() && slot.inserted_version > token.version)
+			.map(|(index, slot)| Key {
+				index,
+				generation: slot.generation,
+			})
+			.collect();
+		let removed = self
+			.changes
+			.removed
+			.iter()
+			.filter(|(_, inserted_version)| *inserted_version <= token.version)
+			.map(|(key, _)| *key)
+			.collect();
+		Changes { inserted, removed }
+	}
+
+	/// Splits the [`Arena`] into a [`KeysView`], which can check
+	/// whether [`Key`]s are valid, and a [`ValuesMut`], which can
+	/// mutate items by slot index. Both can be used at the same
+	/// time.
+	pub fn split(&mut self) -> (KeysView<'_>, ValuesMut<'_, T>) {
+		(
+			KeysView::new(&self.slots),
+			ValuesMut::new(&self.slots, &mut self.values),
+		)
 	}
 
 	/// Returns an iterator over shared references to the items in
 	/// the [`Arena`].
 	///
 	/// The most recently added items will be visited first.
-	pub fn iter(&self) -> Iter<T> {
+	pub fn iter(&self) -> Iter<'_, T> {
 		Iter::new(self)
 	}
 
+	/// Returns an iterator over shared references to the items in
+	/// the [`Arena`] whose [`Key`]s aren't in `exclude`.
+	///
+	/// [`Key`]s are compared including their generations, so a
+	/// stale [`Key`] in `exclude` won't hide a new item that's
+	/// using the same slot.
+	///
+	/// The most recently added items will be visited first.
+	pub fn iter_except<'a>(
+		&'a self,
+		exclude: &'a HashSet<Key>,
+	) -> impl Iterator<Item = (Key, &'a T)> {
+		self.iter().filter(move |(key, _)| !exclude.contains(key))
+	}
+
+	/// Returns an iterator over groups of up to `size` items in
+	/// the [`Arena`], in the same order as [`iter`](Self::iter).
+	///
+	/// Every group has exactly `size` items except for possibly
+	/// the last one.
+	///
+	/// # Panics
+	///
+	/// Panics if `size` is `0`.
+	pub fn chunks(&self, size: usize) -> impl Iterator<Item = Vec<(Key, &T)>> {
+		assert!(size != 0, "chunk size must be non-zero");
+		let mut iter = self.iter();
+		std::iter::from_fn(move || {
+			let chunk: Vec<(Key, &T)> = iter.by_ref().take(size).collect();
+			if chunk.is_empty() {
+				None
+			} else {
+				Some(chunk)
+			}
+		})
+	}
+
+	/// Returns an iterator over shared references to the items in
+	/// the [`Arena`], ordered by slot index.
+	pub fn iter_ordered(&self) -> impl Iterator<Item = (Key, &T)> {
+		self.slots
+			.iter()
+			.enumerate()
+			.filter_map(move |(index, slot)| {
+				let key = Key {
+					index,
+					generation: slot.generation,
+				};
+				self.slot_data(index).map(|data| (key, data))
+			})
+	}
+
+	/// Returns an iterator over shared references to the items in
+	/// the [`Arena`] along with their insertion numbers (see
+	/// [`dense_position`](Self::dense_position)).
+	///
+	/// Items are visited in the same order as [`iter`](Self::iter).
+	pub fn iter_with_dense_positions(&self) -> impl Iterator<Item = (Key, usize, &T)> {
+		self.iter()
+			.map(move |(key, data)| (key, self.slots[key.index].dense_position, data))
+	}
+
+	/// Returns an iterator over mutable references to the items in
+	/// the [`Arena`], ordered by slot index.
+	pub fn iter_ordered_mut(&mut self) -> impl Iterator<Item = (Key, &mut T)> {
+		self.slots
+			.iter()
+			.zip(self.values.iter_mut())
+			.enumerate()
+			.filter(|(_, (slot, _))| slot.is_occupied())
+			.map(|(index, (slot, value))| {
+				let key = Key {
+					index,
+					generation: slot.generation,
+				};
+				// SAFETY: occupied slots always have an initialized value
+				(key, unsafe { value.assume_init_mut() })
+			})
+	}
+
+	/// Returns an iterator over shared references to the items in
+	/// the [`Arena`], ordered by generation and then by slot index.
+	///
+	/// Unlike [`iter`](Self::iter), the order items are visited in
+	/// doesn't depend on the order they were inserted in, and removing
+	/// an item doesn't affect the order of the others. This allocates
+	/// a temporary `Vec` of the [`Key`]s to sort them.
+	pub fn iter_by_generation(&self) -> impl Iterator<Item = (Key, &T)> {
+		let mut keys: Vec<Key> = self.iter().map(|(key, _)| key).collect();
+		keys.sort_by_key(|key| (key.generation, key.index));
+		keys.into_iter().map(move |key| (key, &self[key]))
+	}
+
+	/// Returns the [`Key`]s of the items in the [`Arena`], sorted by
+	/// the result of calling `f` on each item.
+	///
+	/// The [`Arena`] itself isn't changed. `f` is called once per
+	/// item, and items that compare equal are kept in the order
+	/// [`iter`](Self::iter) visits them in.
+	pub fn sorted_keys_by<K: Ord>(&self, mut f: impl FnMut(&T) -> K) -> Vec<Key> {
+		let mut keys: Vec<(Key, &T)> = self.iter().collect();
+		keys.sort_by_cached_key(|(_, data)| f(data));
+		keys.into_iter().map(|(key, _)| key).collect()
+	}
+
 	/// Returns an iterator over mutable references to the items in
 	/// the [`Arena`].
 	///
 	/// The most recently added items will be visited first.
-	pub fn iter_mut(&mut self) -> IterMut<T> {
+	pub fn iter_mut(&mut self) -> IterMut<'_, T> {
 		IterMut::new(self)
 	}
 
+	/// Calls `f` with the [`Key`] of and a mutable reference to each
+	/// item in the [`Arena`], stopping early if `f` returns
+	/// [`ControlFlow::Break`].
+	///
+	/// Returns the value `f` broke with, or `None` if it visited
+	/// every item. The most recently added items will be visited
+	/// first.
+	pub fn try_for_each_mut<B>(
+		&mut self,
+		mut f: impl FnMut(Key, &mut T) -> ControlFlow<B>,
+	) -> Option<B> {
+		for (key, data) in self.iter_mut() {
+			if let ControlFlow::Break(value) = f(key, data) {
+				return Some(value);
+			}
+		}
+		None
+	}
+
+	/// Calls `f` with the [`Key`] of and a mutable reference to each
+	/// item in the [`Arena`], along with a function that returns
+	/// `true` if a [`Key`] has an associated item.
+	///
+	/// The most recently added items will be visited first.
+	pub fn walk_mut(&mut self, mut f: impl FnMut(Key, &mut T, &dyn Fn(Key) -> bool)) {
+		// the liveness check only looks at the slot metadata, so it
+		// can be used while an item is mutably borrowed
+		let slots = &self.slots;
+		let is_live = |key: Key| {
+			slots
+				.get(key.index)
+				.is_some_and(|slot| slot.generation == key.generation && slot.is_occupied())
+		};
+		let mut next_occupied_slot_index = self.first_occupied_slot_index;
+		while let Some(index) = next_occupied_slot_index {
+			let slot = &slots[index];
+			next_occupied_slot_index = slot.next_occupied_slot_index();
+			let key = Key {
+				index,
+				generation: slot.generation,
+			};
+			// SAFETY: slots in the occupied list always have an
+			// initialized value
+			let data = unsafe { self.values[index].assume_init_mut() };
+			f(key, data, &is_live);
+		}
+	}
+
+	/// Returns a [`Transaction`] that inserts and removes items in
+	/// a way that can be undone.
+	pub fn transaction(&mut self) -> Transaction<'_, T> {
+		Transaction::new(self)
+	}
+
+	/// Returns a [`CursorMut`] over the items in the [`Arena`],
+	/// which can remove items while traversing them.
+	///
+	/// The most recently added items will be visited first.
+	pub fn cursor_mut(&mut self) -> CursorMut<'_, T> {
+		CursorMut::new(self)
+	}
+
+	/// Consumes the [`Arena`] and returns its items along with their
+	/// [`Key`]s, ordered by slot index.
+	///
+	/// The items can be put back into an [`Arena`] with the same
+	/// [`Key`]s using [`from_indexed`](Self::from_indexed).
+	pub fn into_indexed_vec(mut self) -> Vec<(Key, T)> {
+		let mut items = Vec::with_capacity(self.len());
+		for index in 0..self.slots.len() {
+			let key = Key {
+				index,
+				generation: self.slots[index].generation,
+			};
+			if let Some(data) = self.take_slot_data(index) {
+				items.push((key, data));
+			}
+		}
+		items
+	}
+
+	/// Consumes the [`Arena`] and returns an iterator over the
+	/// items it contained.
+	///
+	/// The most recently added items will be visited first.
+	pub fn into_values(mut self) -> impl Iterator<Item = T> {
+		let mut next_occupied_slot_index = self.first_occupied_slot_index;
+		std::iter::from_fn(move || {
+			let index = next_occupied_slot_index?;
+			next_occupied_slot_index = self.slots[index].next_occupied_slot_index();
+			let data = self
+				.take_slot_data(index)
+				.expect("the iterator should not encounter a free slot");
+			Some(data)
+		})
+	}
+
+	/// Removes all elements for which `filter(&element)` returns
+	/// `true`.
+	///
+	/// Unlike [`drain_filter`](Self::drain_filter), every matching
+	/// element is removed, even if the result isn't consumed.
+	/// Returns the removed elements along with the number of
+	/// elements remaining in the [`Arena`].
+	pub fn remove_where(&mut self, filter: impl FnMut(&T) -> bool) -> (Vec<(Key, T)>, usize) {
+		let removed = self.drain_filter(filter).collect();
+		(removed, self.len())
+	}
+
+	/// Removes all elements for which `filter(&element)` returns
+	/// `true`, visiting the slots in ascending index order.
+	///
+	/// Returns the removed elements in that order, each along
+	/// with the index of the slot it was in and its former [`Key`].
+	pub fn remove_where_ordered(
+		&mut self,
+		mut filter: impl FnMut(&T) -> bool,
+	) -> Vec<(usize, Key, T)> {
+		let mut removed = vec![];
+		for index in 0..self.slots.len() {
+			let matches = self.slot_data(index).is_some_and(&mut filter);
+			if matches {
+				let key = Key {
+					index,
+					generation: self.slots[index].generation,
+				};
+				let data = self
+					.remove(key)
+					.expect("the slot was checked to be occupied");
+				removed.push((index, key, data));
+			}
+		}
+		removed
+	}
+
 	/// Returns an iterator that removes and yields all elements
 	/// for which `filter(&element)` returns `true`.
-	pub fn drain_filter<F: FnMut(&T) -> bool>(&mut self, filter: F) -> DrainFilter<T, F> {
+	pub fn drain_filter<F: FnMut(&T) -> bool>(&mut self, filter: F) -> DrainFilter<'_, T, F> {
 		DrainFilter::new(self, filter)
 	}
 }
 
+/// Returns mutable references to items in two different [`Arena`]s
+/// at once.
+///
+/// Returns `None` if either [`Key`] doesn't have an associated item.
+pub fn get_both<'a, A, B>(
+	arena_a: &'a mut Arena<A>,
+	key_a: Key,
+	arena_b: &'a mut Arena<B>,
+	key_b: Key,
+) -> Option<(&'a mut A, &'a mut B)> {
+	Some((arena_a.get_mut(key_a)?, arena_b.get_mut(key_b)?))
+}
+
+/// Returns mutable references to items in three different [`Arena`]s
+/// at once.
+///
+/// Returns `None` if any of the [`Key`]s doesn't have an associated item.
+pub fn get_three<'a, A, B, C>(
+	arena_a: &'a mut Arena<A>,
+	key_a: Key,
+	arena_b: &'a mut Arena<B>,
+	key_b: Key,
+	arena_c: &'a mut Arena<C>,
+	key_c: Key,
+) -> Option<(&'a mut A, &'a mut B, &'a mut C)> {
+	Some((
+		arena_a.get_mut(key_a)?,
+		arena_b.get_mut(key_b)?,
+		arena_c.get_mut(key_c)?,
+	))
+}
+
+impl<T> Drop for Arena<T> {
+	fn drop(&mut self) {
+		if !std::mem::needs_drop::<T>() {
+			return;
+		}
+		for index in 0..self.slots.len() {
+			drop(self.take_slot_data(index));
+		}
+	}
+}
+
+impl<T: Debug> Debug for Arena<T> {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		f.debug_struct("Arena")
+			.field("controller", &self.controller)
+			.field("slots", &self.slots)
+			.field("values", &DebugValues(self))
+			.field("first_occupied_slot_index", &self.first_occupied_slot_index)
+			.field("last_occupied_slot_index", &self.last_occupied_slot_index)
+			.field("len", &self.len)
+			.field("auto_compact_threshold", &self.auto_compact_threshold)
+			.field("next_dense_position", &self.next_dense_position)
+			.field("changes", &self.changes)
+			.finish()
+	}
+}
+
+/// The receivers returned by [`Arena::subscribe`].
+#[derive(Default)]
+struct UtilizationSubscribers {
+	senders: Vec<Sender<(usize, usize)>>,
+	/// The length and capacity most recently sent to the receivers.
+	last_sent: (usize, usize),
+	/// Whether updates are paused by
+	/// [`Arena::pause_utilization_updates`].
+	paused: bool,
+}
+
+/// Frees a reserved [`Key`] when dropped, unless it's disarmed
+/// first.
+struct ReservationGuard {
+	controller: Controller,
+	key: Key,
+	armed: bool,
+}
+
+impl Drop for ReservationGuard {
+	fn drop(&mut self) {
+		if self.armed {
+			self.controller.free(self.key.index);
+		}
+	}
+}
+
+/// Formats the items of an [`Arena`] by slot index, with `None`
+/// for free slots.
+struct DebugValues<'a, T>(&'a Arena<T>);
+
+impl<T: Debug> Debug for DebugValues<'_, T> {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		f.debug_list()
+			.entries((0..self.0.slots.len()).map(|index| self.0.slot_data(index)))
+			.finish()
+	}
+}
+
 impl<T> std::ops::Index<Key> for Arena<T> {
 	type Output = T;
 
@@ -0,0 +1,34 @@
+//! The raw components of an [`Arena`](super::Arena).
+
+use crate::ReservationOrder;
+
+/// The raw components of an [`Arena`](super::Arena).
+///
+/// Returned by [`Arena::into_parts`](super::Arena::into_parts) and
+/// used by [`Arena::from_parts`](super::Arena::from_parts).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArenaParts<T> {
+	/// Every slot in the arena, in order. The length of this
+	/// is the capacity of the arena.
+	pub slots: Vec<SlotParts<T>>,
+	/// The indices of the free slots, in the order they'll
+	/// be reserved.
+	///
+	/// Slots that are neither occupied nor in this list have been
+	/// reserved or retired.
+	pub free_list: Vec<usize>,
+	/// The indices of the occupied slots, in the order they'll
+	/// be iterated over.
+	pub occupied_list: Vec<usize>,
+	/// The order free slots are reserved in.
+	pub reservation_order: ReservationOrder,
+}
+
+/// The raw components of a single slot in an [`Arena`](super::Arena).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SlotParts<T> {
+	/// The current generation of the slot.
+	pub generation: usize,
+	/// The item in the slot, or `None` if the slot isn't occupied.
+	pub data: Option<T>,
+}
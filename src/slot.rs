@@ -1,24 +1,63 @@
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub(crate) enum ArenaSlotState<T> {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ArenaSlotState {
 	Free,
 	Occupied {
-		data: T,
 		previous_occupied_slot_index: Option<usize>,
 		next_occupied_slot_index: Option<usize>,
 	},
 }
 
-#[derive(Debug)]
-pub(crate) struct ArenaSlot<T> {
-	pub(crate) state: ArenaSlotState<T>,
+/// The metadata for a slot in an [`Arena`](crate::Arena).
+///
+/// The item itself is stored separately, so that iterating over
+/// items doesn't have to load the metadata for every slot.
+#[derive(Debug, Clone)]
+pub(crate) struct ArenaSlot {
+	pub(crate) state: ArenaSlotState,
 	pub(crate) generation: usize,
+	/// The insertion number of the item in the slot. Only meaningful
+	/// while the slot is occupied.
+	pub(crate) dense_position: usize,
+	/// The change tracking version the item in the slot was
+	/// inserted at. Only meaningful while the slot is occupied.
+	pub(crate) inserted_version: usize,
 }
 
-impl<T> ArenaSlot<T> {
+impl ArenaSlot {
 	pub(crate) fn new() -> Self {
 		Self {
 			state: ArenaSlotState::Free,
 			generation: 0,
+			dense_position: 0,
+			inserted_version: 0,
+		}
+	}
+
+	pub(crate) fn is_occupied(&self) -> bool {
+		matches!(self.state, ArenaSlotState::Occupied { .. })
+	}
+
+	pub(crate) fn previous_occupied_slot_index(&self) -> Option<usize> {
+		if let ArenaSlotState::Occupied {
+			previous_occupied_slot_index,
+			..
+		} = &self.state
+		{
+			*previous_occupied_slot_index
+		} else {
+			panic!("expected a slot to be occupied, but it was not");
+		}
+	}
+
+	pub(crate) fn next_occupied_slot_index(&self) -> Option<usize> {
+		if let ArenaSlotState::Occupied {
+			next_occupied_slot_index,
+			..
+		} = &self.state
+		{
+			*next_occupied_slot_index
+		} else {
+			panic!("expected a slot to be occupied, but it was not");
 		}
 	}
 
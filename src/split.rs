@@ -0,0 +1,64 @@
+//! Split views of an [`Arena`](super::Arena).
+
+use std::mem::MaybeUninit;
+
+use crate::{slot::ArenaSlot, Key};
+
+/// A read-only view of which [`Key`]s are valid for an
+/// [`Arena`](super::Arena).
+///
+/// Returned by [`Arena::split`](super::Arena::split).
+#[derive(Debug, Clone, Copy)]
+pub struct KeysView<'a> {
+	slots: &'a [ArenaSlot],
+}
+
+impl<'a> KeysView<'a> {
+	pub(super) fn new(slots: &'a [ArenaSlot]) -> Self {
+		Self { slots }
+	}
+
+	/// Returns `true` if the [`Arena`](super::Arena) has an item
+	/// associated with the [`Key`].
+	pub fn contains(&self, key: Key) -> bool {
+		self.slot_index(key).is_some()
+	}
+
+	/// Returns the index of the slot the item associated with the
+	/// [`Key`] is stored in, or `None` if there is no such item.
+	pub fn slot_index(&self, key: Key) -> Option<usize> {
+		let slot = self.slots.get(key.index)?;
+		if slot.is_occupied() && slot.generation == key.generation {
+			Some(key.index)
+		} else {
+			None
+		}
+	}
+}
+
+/// Gives out mutable references to the items of an
+/// [`Arena`](super::Arena) by slot index.
+///
+/// Returned by [`Arena::split`](super::Arena::split).
+pub struct ValuesMut<'a, T> {
+	slots: &'a [ArenaSlot],
+	values: &'a mut [MaybeUninit<T>],
+}
+
+impl<'a, T> ValuesMut<'a, T> {
+	pub(super) fn new(slots: &'a [ArenaSlot], values: &'a mut [MaybeUninit<T>]) -> Self {
+		Self { slots, values }
+	}
+
+	/// Returns a mutable reference to the item in the slot with
+	/// the given index, or `None` if the slot is free or out of
+	/// bounds.
+	pub fn get_mut(&mut self, slot_index: usize) -> Option<&mut T> {
+		if self.slots.get(slot_index)?.is_occupied() {
+			// SAFETY: occupied slots always have an initialized value
+			Some(unsafe { self.values[slot_index].assume_init_mut() })
+		} else {
+			None
+		}
+	}
+}
@@ -1,8 +1,116 @@
+use std::{
+	alloc::{GlobalAlloc, Layout, System},
+	cell::Cell,
+	collections::{HashMap, HashSet},
+	convert::TryFrom,
+	ops::{ControlFlow, Range},
+	panic::AssertUnwindSafe,
+	rc::Rc,
+};
+
 use crate::{
-	error::{ArenaFull, InsertWithKeyError},
-	Arena,
+	changes::Changes,
+	debug::FragmentationStats,
+	error::{
+		AccessError, ArenaFull, CapacityError, InsertAtError, InsertWithKeyError,
+		InvalidArenaParts, ItemNotFound, KeyTooLarge, TwoMutError,
+	},
+	get_both, get_three,
+	parts::{ArenaParts, SlotParts},
+	slot::ArenaSlot,
+	Arena, ContentionStats, Controller, Key, PackedKey, ReservationOrder, ReserveReport,
+	SingleThreadArena, TypedKey,
 };
 
+/// Counts the bytes allocated by the current thread, so tests can
+/// check how much memory is allocated without any interference
+/// from tests running on other threads.
+struct CountingAllocator;
+
+#[derive(Debug, Default, Clone, Copy)]
+struct AllocationCounts {
+	/// Bytes allocated without being initialized.
+	uninitialized: usize,
+	/// Bytes allocated as zeros.
+	zeroed: usize,
+}
+
+thread_local! {
+	static ALLOCATION_COUNTS: Cell<AllocationCounts> = const {
+		Cell::new(AllocationCounts {
+			uninitialized: 0,
+			zeroed: 0,
+		})
+	};
+	/// Allocations of this size will fail on the current thread.
+	static FAILING_ALLOCATION_SIZE: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+fn count_allocation(size: usize, zeroed: bool) {
+	// the thread local may already be gone if this thread is exiting
+	let _ = ALLOCATION_COUNTS.try_with(|counts| {
+		let mut new_counts = counts.get();
+		if zeroed {
+			new_counts.zeroed += size;
+		} else {
+			new_counts.uninitialized += size;
+		}
+		counts.set(new_counts);
+	});
+}
+
+/// Returns `true` if an allocation of the given size should fail.
+fn allocation_should_fail(size: usize) -> bool {
+	FAILING_ALLOCATION_SIZE
+		.try_with(|failing_size| failing_size.get() == Some(size))
+		.unwrap_or(false)
+}
+
+/// Runs `f`, making any allocation of `size` bytes it does fail.
+fn with_failing_allocation_size<T>(size: usize, f: impl FnOnce() -> T) -> T {
+	FAILING_ALLOCATION_SIZE.with(|failing_size| failing_size.set(Some(size)));
+	let result = f();
+	FAILING_ALLOCATION_SIZE.with(|failing_size| failing_size.set(None));
+	result
+}
+
+/// Returns the result of `f` along with the bytes it allocated.
+fn count_allocations<T>(f: impl FnOnce() -> T) -> (T, AllocationCounts) {
+	ALLOCATION_COUNTS.with(|counts| counts.set(AllocationCounts::default()));
+	let result = f();
+	(result, ALLOCATION_COUNTS.with(Cell::get))
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+	unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+		if allocation_should_fail(layout.size()) {
+			return std::ptr::null_mut();
+		}
+		count_allocation(layout.size(), false);
+		System.alloc(layout)
+	}
+
+	unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+		if allocation_should_fail(layout.size()) {
+			return std::ptr::null_mut();
+		}
+		count_allocation(layout.size(), true);
+		System.alloc_zeroed(layout)
+	}
+
+	unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+		count_allocation(new_size.saturating_sub(layout.size()), false);
+		System.realloc(ptr, layout, new_size)
+	}
+
+	unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+		System.dealloc(ptr, layout)
+	}
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
 #[test]
 fn controller() {
 	let arena = Arena::<()>::new(1);
@@ -300,3 +408,3115 @@ fn drain_filter() {
 	assert_eq!(arena.get(key5), Some(&5));
 	assert_eq!(arena.get(key6), None);
 }
+
+#[test]
+fn try_reserve_in_range() {
+	let arena = Arena::<()>::new(6);
+	let controller = arena.controller();
+	// we should get a slot within the requested range
+	let key = controller.try_reserve_in_range(2..4).unwrap();
+	assert!((2..4).contains(&key.index));
+	let key = controller.try_reserve_in_range(2..4).unwrap();
+	assert!((2..4).contains(&key.index));
+	// once the range is exhausted, reserving in it should fail
+	assert_eq!(controller.try_reserve_in_range(2..4), Err(ArenaFull));
+	// the slots we skipped over should still be available
+	// in their original order
+	assert_eq!(controller.try_reserve().unwrap().index, 0);
+	assert_eq!(controller.try_reserve().unwrap().index, 1);
+	assert_eq!(controller.try_reserve().unwrap().index, 4);
+	assert_eq!(controller.try_reserve().unwrap().index, 5);
+	assert_eq!(controller.try_reserve(), Err(ArenaFull));
+}
+
+#[test]
+fn try_reserve_in_range_concurrently() {
+	const CAPACITY: usize = 1000;
+	const RANGE: Range<usize> = 900..1000;
+	const RESERVERS: usize = 4;
+	const RESERVATIONS_PER_THREAD: usize = 225;
+	const ROUNDS: usize = 20;
+	for _ in 0..ROUNDS {
+		let arena = Arena::<()>::new(CAPACITY);
+		let controller = arena.controller();
+		let barrier = std::sync::Barrier::new(RESERVERS + 1);
+		let (range_keys, other_keys) = std::thread::scope(|scope| {
+			let range_reserver = {
+				let controller = controller.clone();
+				let barrier = &barrier;
+				scope.spawn(move || {
+					barrier.wait();
+					RANGE
+						.map(|_| controller.try_reserve_in_range(RANGE).unwrap())
+						.collect::<Vec<_>>()
+				})
+			};
+			// the other threads reserve every slot outside of the
+			// range, so they'd run out if reserving in the range
+			// took any of those slots off the free list, even briefly
+			let reservers: Vec<_> = (0..RESERVERS)
+				.map(|_| {
+					let controller = controller.clone();
+					let barrier = &barrier;
+					scope.spawn(move || {
+						barrier.wait();
+						(0..RESERVATIONS_PER_THREAD)
+							.map(|_| controller.try_reserve().unwrap())
+							.collect::<Vec<_>>()
+					})
+				})
+				.collect();
+			(
+				range_reserver.join().unwrap(),
+				reservers
+					.into_iter()
+					.flat_map(|handle| handle.join().unwrap())
+					.collect::<Vec<_>>(),
+			)
+		});
+		assert!(range_keys.iter().all(|key| RANGE.contains(&key.index)));
+		let slot_indices: HashSet<usize> = range_keys
+			.iter()
+			.chain(&other_keys)
+			.map(|key| key.index)
+			.collect();
+		assert_eq!(slot_indices.len(), CAPACITY);
+		assert_eq!(controller.free_count(), 0);
+		// the slots reserved in the range are still in the free
+		// list, but they're skipped when they reach the front
+		for key in &other_keys {
+			controller.free(key.index);
+		}
+		for _ in &other_keys {
+			assert!(!RANGE.contains(&controller.try_reserve().unwrap().index));
+		}
+		assert_eq!(controller.try_reserve(), Err(ArenaFull));
+	}
+}
+
+#[test]
+fn from_hashmap() {
+	let mut map = HashMap::new();
+	map.insert("a", 1);
+	map.insert("b", 2);
+	map.insert("c", 3);
+	let (arena, keys) = Arena::from_hashmap(map);
+	// the arena should be sized to fit the map exactly
+	assert_eq!(arena.capacity(), 3);
+	assert_eq!(arena.len(), 3);
+	// each map key should point to its original value
+	assert_eq!(arena.get(keys["a"]), Some(&1));
+	assert_eq!(arena.get(keys["b"]), Some(&2));
+	assert_eq!(arena.get(keys["c"]), Some(&3));
+}
+
+#[test]
+fn dedup_by_key() {
+	let mut arena = Arena::new(5);
+	let key1 = arena.insert((1, 'a')).unwrap();
+	let key2 = arena.insert((2, 'b')).unwrap();
+	let key3 = arena.insert((1, 'c')).unwrap();
+	let key4 = arena.insert((2, 'd')).unwrap();
+	let key5 = arena.insert((3, 'e')).unwrap();
+	let duplicates = arena.dedup_by_key(|(num, _)| *num);
+	// the newest item for each key is visited first, so it
+	// should be the one that's kept
+	assert_eq!(duplicates, vec![(key2, key4), (key1, key3)]);
+	assert_eq!(arena.len(), 3);
+	assert_eq!(arena.get(key1), None);
+	assert_eq!(arena.get(key2), None);
+	assert_eq!(arena.get(key3), Some(&(1, 'c')));
+	assert_eq!(arena.get(key4), Some(&(2, 'd')));
+	assert_eq!(arena.get(key5), Some(&(3, 'e')));
+}
+
+#[test]
+fn is_reserved() {
+	let mut arena = Arena::new(3);
+	let controller = arena.controller();
+	let key = controller.try_reserve().unwrap();
+	// a reserved key should be reported as reserved,
+	// but it shouldn't have an item yet
+	assert!(arena.is_reserved(key));
+	assert_eq!(arena.get(key), None);
+	// once we insert with the key, it's no longer just reserved
+	arena.insert_with_key(key, 1).unwrap();
+	assert!(!arena.is_reserved(key));
+	// after removing the item, the slot is free again
+	arena.remove(key);
+	assert!(!arena.is_reserved(key));
+}
+
+#[test]
+fn cursor_mut() {
+	let mut arena = Arena::new(6);
+	let keys: Vec<_> = (1..=6).map(|i| arena.insert(i).unwrap()).collect();
+	let mut cursor = arena.cursor_mut();
+	// there's nothing to remove before the first call to `next`
+	assert_eq!(cursor.remove_current(), None);
+	let mut remove = true;
+	while let Some((_, num)) = cursor.next() {
+		*num *= 10;
+		if remove {
+			assert!(cursor.remove_current().is_some());
+			// the current item can only be removed once
+			assert_eq!(cursor.remove_current(), None);
+		}
+		remove = !remove;
+	}
+	// every other item should be removed, and the rest
+	// should be modified
+	assert_eq!(arena.len(), 3);
+	assert_eq!(arena.get(keys[0]), Some(&10));
+	assert_eq!(arena.get(keys[1]), None);
+	assert_eq!(arena.get(keys[2]), Some(&30));
+	assert_eq!(arena.get(keys[3]), None);
+	assert_eq!(arena.get(keys[4]), Some(&50));
+	assert_eq!(arena.get(keys[5]), None);
+}
+
+#[test]
+fn split() {
+	let mut arena = Arena::new(3);
+	let key1 = arena.insert(1).unwrap();
+	let key2 = arena.insert(2).unwrap();
+	let key3 = arena.insert(3).unwrap();
+	arena.remove(key3);
+	let (keys, mut values) = arena.split();
+	// we should be able to mutate one item based on
+	// the liveness of others
+	if keys.contains(key2) && !keys.contains(key3) {
+		*values.get_mut(keys.slot_index(key1).unwrap()).unwrap() += 10;
+	}
+	assert_eq!(keys.slot_index(key3), None);
+	assert_eq!(values.get_mut(key3.index), None);
+	assert_eq!(arena.get(key1), Some(&11));
+}
+
+#[test]
+fn is_same_slot() {
+	let mut arena = Arena::new(2);
+	let key1 = arena.insert(1).unwrap();
+	let key2 = arena.insert(2).unwrap();
+	arena.remove(key1);
+	let key3 = arena.insert(3).unwrap();
+	// the new key reuses the removed key's slot, but it
+	// refers to a different item
+	assert!(key1.is_same_slot(&key3));
+	assert_ne!(key1, key3);
+	assert!(!key1.is_same_slot(&key2));
+}
+
+#[test]
+fn load_factor() {
+	assert_eq!(Arena::<()>::new(0).load_factor(), 0.0);
+	let mut arena = Arena::new(4);
+	assert_eq!(arena.load_factor(), 0.0);
+	arena.insert(1).unwrap();
+	arena.insert(2).unwrap();
+	assert_eq!(arena.load_factor(), 0.5);
+	arena.insert(3).unwrap();
+	arena.insert(4).unwrap();
+	assert_eq!(arena.load_factor(), 1.0);
+}
+
+#[test]
+fn contains() {
+	let mut arena = Arena::new(1);
+	let key1 = arena.insert(1).unwrap();
+	assert!(arena.contains(key1));
+	arena.remove(key1);
+	assert!(!arena.contains(key1));
+	let key2 = arena.insert(2).unwrap();
+	assert!(!arena.contains(key1));
+	assert!(arena.contains(key2));
+}
+
+#[test]
+fn get_two_mut() {
+	let mut arena = Arena::new(3);
+	let key1 = arena.insert(1).unwrap();
+	let key2 = arena.insert(2).unwrap();
+	let key3 = arena.insert(3).unwrap();
+	arena.remove(key3);
+	// we should be able to mutate two items at once,
+	// in either order
+	let (a, b) = arena.get_two_mut(key1, key2).unwrap();
+	std::mem::swap(a, b);
+	let (a, b) = arena.get_two_mut(key2, key1).unwrap();
+	assert_eq!((*a, *b), (1, 2));
+	// each precondition should have its own error
+	assert_eq!(arena.get_two_mut(key1, key1), Err(TwoMutError::SameSlot));
+	assert_eq!(arena.get_two_mut(key3, key1), Err(TwoMutError::FirstDead));
+	assert_eq!(arena.get_two_mut(key1, key3), Err(TwoMutError::SecondDead));
+}
+
+#[test]
+fn get_two_mut_indexed() {
+	let mut arena = Arena::new(3);
+	let key1 = arena.insert(1).unwrap();
+	let key2 = arena.insert(2).unwrap();
+	let key3 = arena.insert(3).unwrap();
+	arena.remove(key3);
+	let ((a_key, a), (b_key, b)) = arena.get_two_mut_indexed(key2, key1).unwrap();
+	assert_eq!((a_key, b_key), (key2, key1));
+	*a += 10;
+	*b += 20;
+	assert_eq!(arena.get(key1), Some(&21));
+	assert_eq!(arena.get(key2), Some(&12));
+	assert!(arena.get_two_mut_indexed(key1, key3).is_none());
+	assert!(arena.get_two_mut_indexed(key1, key1).is_none());
+}
+
+#[test]
+fn filled() {
+	let mut arena = Arena::filled(5, 0);
+	assert_eq!(arena.len(), 5);
+	assert!(arena.iter().all(|(_, num)| *num == 0));
+	// the arena should be full
+	assert_eq!(arena.controller().try_reserve(), Err(ArenaFull));
+	assert_eq!(arena.insert(1), Err(ArenaFull));
+	// items should be visited in reverse slot order
+	let keys: Vec<_> = arena.iter().map(|(key, _)| key).collect();
+	assert_eq!(
+		keys.iter().map(|key| key.index).collect::<Vec<_>>(),
+		vec![4, 3, 2, 1, 0]
+	);
+	// the occupied list should stay consistent after removals
+	arena.remove(keys[2]);
+	arena.remove(keys[0]);
+	assert_eq!(arena.iter().count(), 3);
+	let key = arena.insert(1).unwrap();
+	assert_eq!(arena.get(key), Some(&1));
+	assert_eq!(arena.iter().count(), 4);
+
+	let mut counter = 0;
+	let arena = Arena::filled_with(3, || {
+		counter += 1;
+		counter
+	});
+	assert_eq!(
+		arena.iter().map(|(_, num)| *num).collect::<Vec<_>>(),
+		vec![3, 2, 1]
+	);
+	assert_eq!(Arena::filled(0, ()).len(), 0);
+}
+
+#[test]
+fn get_disjoint_mut() {
+	let mut arena = Arena::new(4);
+	let key1 = arena.insert(1).unwrap();
+	let key2 = arena.insert(2).unwrap();
+	let key3 = arena.insert(3).unwrap();
+	let key4 = arena.insert(4).unwrap();
+	arena.remove(key4);
+	let [a, b, c] = arena.get_disjoint_mut([key3, key1, key2]).unwrap();
+	*a += 10;
+	*b += 20;
+	*c += 30;
+	assert_eq!(arena.get(key1), Some(&21));
+	assert_eq!(arena.get(key2), Some(&32));
+	assert_eq!(arena.get(key3), Some(&13));
+	// aliasing or dead keys should be rejected
+	assert!(arena.get_disjoint_mut([key1, key2, key1]).is_none());
+	assert!(arena.get_disjoint_mut([key1, key4]).is_none());
+	// the unchecked version should behave the same for valid keys
+	let [a, b] = unsafe { arena.get_disjoint_unchecked_mut([key1, key3]) };
+	std::mem::swap(a, b);
+	assert_eq!(arena.get(key1), Some(&13));
+	assert_eq!(arena.get(key3), Some(&21));
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "Multiple keys refer to the same slot")]
+fn get_disjoint_unchecked_mut_debug_assertions() {
+	let mut arena = Arena::new(2);
+	let key = arena.insert(1).unwrap();
+	let _ = unsafe { arena.get_disjoint_unchecked_mut([key, key]) };
+}
+
+#[test]
+fn modify_many() {
+	let mut arena = Arena::new(4);
+	let key1 = arena.insert(1).unwrap();
+	let key2 = arena.insert(2).unwrap();
+	let key3 = arena.insert(3).unwrap();
+	arena.remove(key2);
+	let mut positions = vec![];
+	arena.modify_many([key3, key2, key1], |position, num| {
+		positions.push(position);
+		*num *= 10;
+	});
+	// the dead key should be skipped
+	assert_eq!(positions, vec![0, 2]);
+	assert_eq!(arena.get(key1), Some(&10));
+	assert_eq!(arena.get(key3), Some(&30));
+	// a stale key for a reused slot doesn't alias the live one
+	let key4 = arena.insert(4).unwrap();
+	assert!(key4.is_same_slot(&key2));
+	arena.modify_many([key2, key4], |_, num| *num += 1);
+	assert_eq!(arena.get(key4), Some(&5));
+}
+
+#[test]
+#[should_panic(expected = "Multiple keys refer to the same slot")]
+fn modify_many_aliasing() {
+	let mut arena = Arena::new(2);
+	let key = arena.insert(1).unwrap();
+	arena.modify_many([key, key], |_, _| {});
+}
+
+#[test]
+fn count() {
+	let mut arena = Arena::new(6);
+	for i in 1..=6 {
+		arena.insert(i).unwrap();
+	}
+	assert_eq!(arena.count(|num| num % 2 == 0), 3);
+	assert_eq!(arena.count(|num| *num > 4), 2);
+	assert_eq!(arena.count(|num| *num > 6), 0);
+}
+
+#[test]
+fn any_all() {
+	let mut arena = Arena::new(4);
+	for i in 1..=4 {
+		arena.insert(i).unwrap();
+	}
+	assert!(arena.any(|num| *num == 3));
+	assert!(!arena.any(|num| *num == 5));
+	assert!(arena.all(|num| *num > 0));
+	assert!(!arena.all(|num| *num < 4));
+	// `any` should stop after the first match
+	let calls = Cell::new(0);
+	assert!(arena.any(|num| {
+		calls.set(calls.get() + 1);
+		*num == 3
+	}));
+	assert_eq!(calls.get(), 2);
+	// `all` should stop after the first mismatch
+	let calls = Cell::new(0);
+	assert!(!arena.all(|num| {
+		calls.set(calls.get() + 1);
+		*num == 4
+	}));
+	assert_eq!(calls.get(), 2);
+	// an empty arena trivially satisfies `all` but not `any`
+	let arena = Arena::<i32>::new(1);
+	assert!(arena.all(|_| false));
+	assert!(!arena.any(|_| true));
+}
+
+#[test]
+fn reservation_order() {
+	// by default, the most recently freed slot is reused first
+	let mut arena = Arena::new(4);
+	let keys: Vec<_> = (0..4).map(|i| arena.insert(i).unwrap()).collect();
+	arena.remove(keys[2]);
+	arena.remove(keys[0]);
+	arena.remove(keys[3]);
+	let reused: Vec<_> = (0..3).map(|i| arena.insert(i).unwrap().index).collect();
+	assert_eq!(reused, vec![3, 0, 2]);
+
+	// with fifo ordering, the least recently freed slot is reused first
+	let mut arena = Arena::with_reservation_order(4, ReservationOrder::Fifo);
+	let keys: Vec<_> = (0..4).map(|i| arena.insert(i).unwrap()).collect();
+	assert_eq!(
+		keys.iter().map(|key| key.index).collect::<Vec<_>>(),
+		vec![0, 1, 2, 3]
+	);
+	assert_eq!(arena.insert(4), Err(ArenaFull));
+	arena.remove(keys[2]);
+	arena.remove(keys[0]);
+	arena.remove(keys[3]);
+	let reused: Vec<_> = (0..3).map(|i| arena.insert(i).unwrap()).collect();
+	assert_eq!(
+		reused.iter().map(|key| key.index).collect::<Vec<_>>(),
+		vec![2, 0, 3]
+	);
+	assert_eq!(arena.insert(3), Err(ArenaFull));
+	// the reused slots should have new generations
+	assert_eq!(reused[0].generation, 1);
+	assert_eq!(arena.get(keys[2]), None);
+	assert_eq!(arena.get(reused[0]), Some(&0));
+	// a fifo arena should work with zero capacity
+	let arena = Arena::<()>::with_reservation_order(0, ReservationOrder::Fifo);
+	assert_eq!(arena.controller().try_reserve(), Err(ArenaFull));
+}
+
+#[test]
+fn get_mut_with_key() {
+	let mut arena = Arena::new(2);
+	let key1 = arena.insert(1).unwrap();
+	let key2 = arena.insert(2).unwrap();
+	let (key, num) = arena.get_mut_with_key(key2).unwrap();
+	assert_eq!(key, key2);
+	*num = 3;
+	assert_eq!(arena.get(key2), Some(&3));
+	arena.remove(key1);
+	assert!(arena.get_mut_with_key(key1).is_none());
+}
+
+#[test]
+fn truncate_oldest() {
+	let mut arena = Arena::new(6);
+	let keys: Vec<_> = (0..6).map(|i| arena.insert(i).unwrap()).collect();
+	arena.remove(keys[3]);
+	arena.truncate_oldest(3);
+	// the newest 3 items should be kept
+	assert_eq!(
+		arena.iter().map(|(_, num)| *num).collect::<Vec<_>>(),
+		vec![5, 4, 2]
+	);
+	// removing the oldest items should free up space
+	// for new ones
+	let key = arena.insert(6).unwrap();
+	arena.truncate_oldest(2);
+	assert_eq!(
+		arena
+			.iter()
+			.map(|(key, num)| (key, *num))
+			.collect::<Vec<_>>(),
+		vec![(key, 6), (keys[5], 5)]
+	);
+	// truncating to a larger length shouldn't remove anything
+	arena.truncate_oldest(10);
+	assert_eq!(arena.len(), 2);
+	arena.truncate_oldest(0);
+	assert!(arena.is_empty());
+	// the arena should still work normally after being emptied
+	let key = arena.insert(7).unwrap();
+	arena.truncate_oldest(1);
+	assert_eq!(arena.get(key), Some(&7));
+}
+
+#[test]
+fn rotate_occupied() {
+	let mut arena = Arena::new(4);
+	let keys: Vec<_> = (0..4).map(|i| arena.insert(i).unwrap()).collect();
+	// after rotating by 1, the previously second item
+	// should be visited first
+	arena.rotate_occupied(1);
+	assert_eq!(
+		arena.iter().map(|(_, num)| *num).collect::<Vec<_>>(),
+		vec![2, 1, 0, 3]
+	);
+	// rotation should wrap around
+	arena.rotate_occupied(6);
+	assert_eq!(
+		arena.iter().map(|(_, num)| *num).collect::<Vec<_>>(),
+		vec![0, 3, 2, 1]
+	);
+	// keys should be unaffected
+	for (i, key) in keys.iter().enumerate() {
+		assert_eq!(arena.get(*key), Some(&i));
+	}
+	// the list should stay consistent for removals and insertions
+	arena.remove(keys[0]);
+	arena.remove(keys[1]);
+	arena.insert(4).unwrap();
+	assert_eq!(
+		arena.iter().map(|(_, num)| *num).collect::<Vec<_>>(),
+		vec![4, 3, 2]
+	);
+	arena.truncate_oldest(1);
+	assert_eq!(
+		arena.iter().map(|(_, num)| *num).collect::<Vec<_>>(),
+		vec![4]
+	);
+}
+
+#[test]
+fn partition() {
+	let mut arena = Arena::new(10);
+	for i in 1..=7 {
+		arena.insert(i).unwrap();
+	}
+	let (even, odd) = arena.partition(|num| num % 2 == 0);
+	assert_eq!(even.capacity(), 3);
+	assert_eq!(odd.capacity(), 4);
+	// the items should keep their relative order
+	assert_eq!(
+		even.iter().map(|(_, num)| *num).collect::<Vec<_>>(),
+		vec![6, 4, 2]
+	);
+	assert_eq!(
+		odd.iter().map(|(_, num)| *num).collect::<Vec<_>>(),
+		vec![7, 5, 3, 1]
+	);
+}
+
+#[test]
+fn borrowed_keys() {
+	let mut arena = Arena::new(2);
+	let keys = vec![arena.insert(1).unwrap(), arena.insert(2).unwrap()];
+	// accessors should accept both keys and references to keys
+	assert_eq!(arena.get(keys[0]), Some(&1));
+	for key in &keys {
+		assert!(arena.contains(key));
+		*arena.get_mut(key).unwrap() *= 10;
+	}
+	let values: Vec<_> = keys.iter().map(|key| arena.get(key).copied()).collect();
+	assert_eq!(values, vec![Some(10), Some(20)]);
+	for key in &keys {
+		assert!(arena.remove(key).is_some());
+	}
+	assert!(arena.is_empty());
+}
+
+#[test]
+fn iter_by_generation() {
+	let mut arena = Arena::new(4);
+	let key1 = arena.insert(1).unwrap();
+	let key2 = arena.insert(2).unwrap();
+	let key3 = arena.insert(3).unwrap();
+	arena.remove(key1);
+	// this item reuses slot 0, so it has a higher generation
+	let key4 = arena.insert(4).unwrap();
+	let key5 = arena.insert(5).unwrap();
+	assert_eq!(
+		arena.iter_by_generation().collect::<Vec<_>>(),
+		vec![(key2, &2), (key3, &3), (key5, &5), (key4, &4)]
+	);
+	// removing an item shouldn't change the order of the others
+	arena.remove(key3);
+	assert_eq!(
+		arena.iter_by_generation().collect::<Vec<_>>(),
+		vec![(key2, &2), (key5, &5), (key4, &4)]
+	);
+}
+
+#[test]
+fn into_values() {
+	let mut arena = Arena::new(4);
+	let key1 = arena.insert("a".to_string()).unwrap();
+	arena.insert("b".to_string()).unwrap();
+	arena.insert("c".to_string()).unwrap();
+	arena.remove(key1);
+	assert_eq!(
+		arena.into_values().collect::<Vec<_>>(),
+		vec!["c".to_string(), "b".to_string()]
+	);
+}
+
+#[test]
+fn grow() {
+	let mut arena = Arena::new(2);
+	let key1 = arena.insert(1).unwrap();
+	let key2 = arena.insert(2).unwrap();
+	arena.grow(2);
+	assert_eq!(arena.capacity(), 4);
+	assert_eq!(arena.controller().capacity(), 4);
+	// existing items should be unaffected
+	assert_eq!(arena.get(key1), Some(&1));
+	assert_eq!(arena.get(key2), Some(&2));
+	// the new slots should be usable
+	let key3 = arena.insert(3).unwrap();
+	let key4 = arena.insert(4).unwrap();
+	assert_eq!((key3.index, key4.index), (2, 3));
+	assert_eq!(arena.insert(5), Err(ArenaFull));
+	// previously freed slots should still be usable after growing
+	arena.remove(key1);
+	arena.grow(1);
+	let mut indices: Vec<_> = (0..2).map(|i| arena.insert(i).unwrap().index).collect();
+	indices.sort_unstable();
+	assert_eq!(indices, vec![0, 4]);
+	assert_eq!(arena.insert(6), Err(ArenaFull));
+
+	// growing should work the same with fifo reservation order
+	let mut arena = Arena::with_reservation_order(2, ReservationOrder::Fifo);
+	let key1 = arena.insert(1).unwrap();
+	arena.insert(2).unwrap();
+	arena.remove(key1);
+	arena.grow(2);
+	let indices: Vec<_> = (0..3).map(|i| arena.insert(i).unwrap().index).collect();
+	assert_eq!(indices, vec![0, 2, 3]);
+	assert_eq!(arena.insert(4), Err(ArenaFull));
+}
+
+#[test]
+#[should_panic(expected = "cannot grow an arena while other controllers for it exist")]
+fn grow_with_controller() {
+	let mut arena = Arena::<()>::new(1);
+	let _controller = arena.controller();
+	arena.grow(1);
+}
+
+#[test]
+fn reserve_or_grow() {
+	let mut arena = Arena::new(1);
+	let key1 = arena.reserve_or_grow(2);
+	arena.insert_with_key(key1, 1).unwrap();
+	assert_eq!(arena.capacity(), 1);
+	// reserving on a full arena should grow it
+	let key2 = arena.reserve_or_grow(2);
+	assert_eq!(arena.capacity(), 3);
+	assert!((1..3).contains(&key2.index));
+	arena.insert_with_key(key2, 2).unwrap();
+	assert_eq!(arena.get(key2), Some(&2));
+}
+
+#[test]
+fn debug_slot() {
+	let mut arena = Arena::new(3);
+	let key1 = arena.insert(1).unwrap();
+	let key2 = arena.insert(2).unwrap();
+	let first = arena.debug_slot(key2.index).unwrap();
+	assert!(first.is_occupied());
+	assert_eq!(first.data(), Some(&2));
+	assert_eq!(first.prev_link(), None);
+	assert_eq!(first.next_link(), Some(key1.index));
+	let second = arena.debug_slot(key1.index).unwrap();
+	assert_eq!(second.prev_link(), Some(key2.index));
+	assert_eq!(second.next_link(), None);
+	arena.remove(key1);
+	let removed = arena.debug_slot(key1.index).unwrap();
+	assert!(!removed.is_occupied());
+	assert_eq!(removed.generation(), 1);
+	assert_eq!(removed.data(), None);
+	assert!(arena.debug_slot(3).is_none());
+}
+
+#[test]
+fn update() {
+	let mut arena = Arena::new(2);
+	let key1 = arena.insert(1).unwrap();
+	let key2 = arena.insert(2).unwrap();
+	assert_eq!(arena.update(key1, |num| num * 10), Ok(()));
+	// the same key should resolve to the new value
+	assert_eq!(arena.get(key1), Some(&10));
+	assert_eq!(arena.get(key2), Some(&2));
+	arena.remove(key2);
+	assert_eq!(arena.update(key2, |num| num * 10), Err(ItemNotFound));
+}
+
+#[test]
+fn update_order() {
+	let mut arena = Arena::new(3);
+	let keys: Vec<_> = (0..3).map(|i| arena.insert(i).unwrap()).collect();
+	// updating items shouldn't change the iteration order
+	for key in &keys {
+		arena.update(*key, |num| num + 10).unwrap();
+	}
+	assert_eq!(
+		arena.iter().collect::<Vec<_>>(),
+		vec![(keys[2], &12), (keys[1], &11), (keys[0], &10)]
+	);
+}
+
+#[test]
+fn update_panic() {
+	let mut arena = Arena::new(3);
+	let key1 = arena.insert(1).unwrap();
+	let key2 = arena.insert(2).unwrap();
+	let key3 = arena.insert(3).unwrap();
+	let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+		arena.update(key2, |_| panic!("update failed")).ok();
+	}));
+	assert!(result.is_err());
+	// the item should be removed, and the key should be dead
+	assert_eq!(arena.get(key2), None);
+	assert_eq!(arena.len(), 2);
+	assert_eq!(
+		arena.iter().collect::<Vec<_>>(),
+		vec![(key3, &3), (key1, &1)]
+	);
+	// the slot should be reusable
+	let key4 = arena.insert(4).unwrap();
+	assert!(key4.is_same_slot(&key2));
+	assert_eq!(arena.get(key4), Some(&4));
+	assert_eq!(arena.get(key2), None);
+}
+
+#[test]
+fn retire() {
+	let mut arena = Arena::new(3);
+	let key1 = arena.insert(1).unwrap();
+	let key2 = arena.insert(2).unwrap();
+	assert_eq!(arena.retire(key1), Some(1));
+	assert_eq!(arena.get(key1), None);
+	assert_eq!(arena.retire(key1), None);
+	// a reserved key's slot can be retired too
+	let key3 = arena.controller().try_reserve().unwrap();
+	assert_eq!(arena.retire(key3), None);
+	assert!(!arena.is_reserved(key3));
+	assert_eq!(
+		arena.insert_with_key(key3, 3),
+		Err(InsertWithKeyError::InvalidKey)
+	);
+	// the retired slots should never be reserved again
+	arena.remove(key2);
+	let key4 = arena.insert(4).unwrap();
+	assert!(key4.is_same_slot(&key2));
+	assert_eq!(arena.insert(5), Err(ArenaFull));
+	assert_eq!(arena.iter().collect::<Vec<_>>(), vec![(key4, &4)]);
+}
+
+#[test]
+fn retired_slots_cannot_be_refilled() {
+	let mut arena = Arena::new(2);
+	let key = arena.insert(1).unwrap();
+	arena.retire(key);
+	// a key with the slot's current generation still can't be
+	// used to fill it
+	let rebuilt_key = Key {
+		index: key.index,
+		generation: key.generation + 1,
+	};
+	assert!(!arena.is_reserved(rebuilt_key));
+	assert_eq!(
+		arena.insert_with_key(rebuilt_key, 2),
+		Err(InsertWithKeyError::KeyNotReserved)
+	);
+	assert_eq!(
+		arena.insert_at(key.index, key.generation + 1, 2),
+		Err(InsertAtError::Retired)
+	);
+	assert!(arena.is_empty());
+	// the slot never goes back on the free list
+	arena.remove(rebuilt_key);
+	arena.retain(|_| false);
+	assert_eq!(arena.controller().free_list_order(), vec![1]);
+	arena.insert(3).unwrap();
+	assert_eq!(arena.insert(4), Err(ArenaFull));
+}
+
+#[test]
+fn iter_ordered() {
+	let mut arena = Arena::new(4);
+	let keys: Vec<_> = (0..4).map(|i| arena.insert(i).unwrap()).collect();
+	arena.remove(keys[1]);
+	// items should be visited in slot order rather than
+	// newest first
+	assert_eq!(
+		arena.iter_ordered().collect::<Vec<_>>(),
+		vec![(keys[0], &0), (keys[2], &2), (keys[3], &3)]
+	);
+	// we should be able to pack mutated values in slot order
+	let packed: Vec<_> = arena
+		.iter_ordered_mut()
+		.map(|(_, num)| {
+			*num *= 10;
+			*num
+		})
+		.collect();
+	assert_eq!(packed, vec![0, 20, 30]);
+	assert_eq!(arena.get(keys[3]), Some(&30));
+}
+
+#[test]
+fn get_raw() {
+	let mut arena = Arena::new(2);
+	let key = arena.insert(1).unwrap();
+	assert_eq!(arena.get_raw(key.index, key.generation), Some(&1));
+	assert_eq!(arena.get_raw(key.index, key.generation + 1), None);
+	assert_eq!(arena.get_raw(1, 0), None);
+	assert_eq!(arena.get_raw(2, 0), None);
+	arena.remove(key);
+	let key = arena.insert(2).unwrap();
+	assert_eq!(arena.get_raw(key.index, 0), None);
+	assert_eq!(arena.get_raw(key.index, 1), Some(&2));
+}
+
+#[test]
+fn reserve_report() {
+	let mut arena = Arena::new(1);
+	let controller = arena.controller();
+	let key = match controller.reserve_report() {
+		ReserveReport::Available(key) => key,
+		ReserveReport::WouldExceedCapacity => panic!("expected a key to be available"),
+	};
+	// the key should actually be reserved
+	assert!(arena.insert_with_key(key, 1).is_ok());
+	assert_eq!(
+		controller.reserve_report(),
+		ReserveReport::WouldExceedCapacity
+	);
+}
+
+#[test]
+fn try_for_each_mut() {
+	let mut arena = Arena::new(4);
+	let keys: Vec<_> = (1..=4).map(|i| arena.insert(i).unwrap()).collect();
+	// we should be able to stop on the first match and
+	// get a value back
+	let mut visited = 0;
+	let result = arena.try_for_each_mut(|key, num| {
+		visited += 1;
+		if *num % 2 == 1 {
+			*num *= 10;
+			ControlFlow::Break(key)
+		} else {
+			ControlFlow::Continue(())
+		}
+	});
+	assert_eq!(result, Some(keys[2]));
+	assert_eq!(visited, 2);
+	assert_eq!(arena.get(keys[2]), Some(&30));
+	// if we never break, we should get `None`
+	let result = arena.try_for_each_mut(|_, num| {
+		*num += 1;
+		ControlFlow::<()>::Continue(())
+	});
+	assert_eq!(result, None);
+	assert_eq!(arena.get(keys[0]), Some(&2));
+}
+
+#[test]
+fn max_occupied_slot() {
+	let mut arena = Arena::new(6);
+	assert_eq!(arena.max_occupied_slot(), None);
+	let keys: Vec<_> = (0..5).map(|i| arena.insert(i).unwrap()).collect();
+	assert_eq!(arena.max_occupied_slot(), Some(4));
+	arena.remove(keys[4]);
+	arena.remove(keys[3]);
+	arena.remove(keys[1]);
+	assert_eq!(arena.max_occupied_slot(), Some(2));
+	arena.remove(keys[2]);
+	assert_eq!(arena.max_occupied_slot(), Some(0));
+	arena.remove(keys[0]);
+	assert_eq!(arena.max_occupied_slot(), None);
+}
+
+#[test]
+fn insert_with_key_index_at_capacity() {
+	// keys from a bigger arena can have an index equal to
+	// the capacity of a smaller one
+	let key = {
+		let mut arena = Arena::new(4);
+		for _ in 0..3 {
+			arena.insert(()).unwrap();
+		}
+		arena.insert(()).unwrap()
+	};
+	let mut arena = Arena::new(3);
+	assert_eq!(key.index, arena.capacity());
+	// inserting should return an error instead of panicking
+	assert_eq!(
+		arena.insert_with_key(key, ()),
+		Err(InsertWithKeyError::InvalidKey)
+	);
+	assert!(arena.is_empty());
+}
+
+#[test]
+fn generation_stats() {
+	let mut arena = Arena::new(4);
+	let stats = arena.generation_stats();
+	assert_eq!((stats.min, stats.max, stats.mean), (0, 0, 0.0));
+	arena.insert(0).unwrap();
+	// repeatedly reusing one slot should drive its generation up
+	for i in 0..10 {
+		let key = arena.insert(i).unwrap();
+		arena.remove(key);
+	}
+	let stats = arena.generation_stats();
+	assert_eq!(stats.min, 0);
+	assert_eq!(stats.max, 10);
+	assert_eq!(stats.mean, 2.5);
+	let stats = Arena::<()>::new(0).generation_stats();
+	assert_eq!((stats.min, stats.max, stats.mean), (0, 0, 0.0));
+}
+
+#[test]
+fn remove_where() {
+	let mut arena = Arena::new(6);
+	let keys: Vec<_> = (1..=6).map(|i| arena.insert(i).unwrap()).collect();
+	let (removed, remaining) = arena.remove_where(|num| num % 3 != 0);
+	assert_eq!(
+		removed,
+		vec![(keys[4], 5), (keys[3], 4), (keys[1], 2), (keys[0], 1)]
+	);
+	assert_eq!(remaining, 2);
+	assert_eq!(arena.len(), 2);
+	// matching elements should be removed even if we
+	// ignore the result
+	let _ = arena.remove_where(|_| true);
+	assert!(arena.is_empty());
+}
+
+#[test]
+fn memory_usage() {
+	let small = Arena::<u64>::new(10);
+	let large = Arena::<u64>::new(20);
+	// both the slots and the controller should scale
+	// with the capacity
+	let slot_size = large.memory_usage() - small.memory_usage();
+	assert!(slot_size >= 10 * std::mem::size_of::<u64>());
+	assert_eq!(large.memory_usage(), 2 * small.memory_usage());
+	let controller_slot_size = large.controller_memory() - small.controller_memory();
+	assert!(controller_slot_size > 0);
+	assert_eq!(
+		Arena::<u64>::new(30).controller_memory() - large.controller_memory(),
+		controller_slot_size
+	);
+	// the controller state should take up some space
+	// even with no slots
+	assert!(Arena::<u64>::new(0).controller_memory() > 0);
+}
+
+#[test]
+fn new_lazy() {
+	let mut arena = Arena::<[u8; 64]>::new_lazy(1_000_000);
+	assert_eq!(arena.capacity(), 1_000_000);
+	// no slots should be initialized until they're used
+	assert_eq!(arena.slots.len(), 0);
+	let key1 = arena.insert([1; 64]).unwrap();
+	let key2 = arena.insert([2; 64]).unwrap();
+	assert_eq!(arena.slots.len(), 2);
+	assert_eq!(arena.get(key1), Some(&[1; 64]));
+	assert_eq!(arena.get(key2), Some(&[2; 64]));
+	// keys for slots that haven't been initialized yet
+	// should behave like keys for free slots
+	let reserved_key = arena.controller().try_reserve().unwrap();
+	assert_eq!(arena.slots.len(), 2);
+	assert!(arena.is_reserved(reserved_key));
+	assert!(!arena.contains(reserved_key));
+	assert_eq!(arena.get(reserved_key), None);
+	assert_eq!(arena.get_mut(reserved_key), None);
+	assert_eq!(arena.remove(reserved_key), None);
+	assert!(!arena.debug_slot(reserved_key.index).unwrap().is_occupied());
+	assert_eq!(arena.generation_stats().max, 0);
+	arena.insert_with_key(reserved_key, [3; 64]).unwrap();
+	assert_eq!(arena.get(reserved_key), Some(&[3; 64]));
+	assert_eq!(arena.slots.len(), 3);
+	assert_eq!(arena.len(), 3);
+}
+
+#[test]
+fn new_lazy_allocation() {
+	const CAPACITY: usize = 1_000_000;
+	let (arena, counts) = count_allocations(|| Arena::<[u8; 64]>::new_lazy(CAPACITY));
+	// the only uninitialized memory should be the space for the
+	// arena's own slots, which is never written to until each slot
+	// is used. the controller's slots start out as all zeros, which
+	// the allocator can provide without touching them
+	assert!(
+		counts.uninitialized
+			<= CAPACITY * (std::mem::size_of::<ArenaSlot>() + std::mem::size_of::<[u8; 64]>())
+				+ 1024
+	);
+	assert!(counts.zeroed >= CAPACITY * Controller::memory_per_slot());
+	// by comparison, an eager arena initializes every slot
+	let (_, counts) = count_allocations(|| Arena::<[u8; 64]>::new(CAPACITY));
+	assert!(
+		counts.uninitialized
+			> CAPACITY
+				* (std::mem::size_of::<ArenaSlot>()
+					+ std::mem::size_of::<[u8; 64]>()
+					+ Controller::memory_per_slot())
+	);
+	drop(arena);
+}
+
+#[test]
+fn new_lazy_controller() {
+	let mut arena = Arena::new_lazy(6);
+	let controller = arena.controller();
+	assert_eq!(controller.free_count(), 6);
+	assert_eq!(controller.free_list_order(), vec![0, 1, 2, 3, 4, 5]);
+	// slots that have never been used are reserved in order, after
+	// any slots that have been freed
+	let key0 = arena.insert(0).unwrap();
+	let key1 = arena.insert(1).unwrap();
+	assert_eq!((key0.index, key1.index), (0, 1));
+	arena.remove(key0);
+	assert_eq!(controller.free_list_order(), vec![0, 2, 3, 4, 5]);
+	assert_eq!(controller.len(), 1);
+	// reserving in a range past the used slots keeps the slots
+	// before it free, without changing the order they're
+	// reserved in
+	let key4 = controller.try_reserve_in_range(4..5).unwrap();
+	assert_eq!(key4.index, 4);
+	assert!(arena.is_slot_occupied(1));
+	assert_eq!(controller.free_count(), 4);
+	assert_eq!(controller.free_list_order(), vec![0, 2, 3, 5]);
+	// compacting only reorders the slots that have been used
+	arena.compact_free_list();
+	assert_eq!(controller.free_list_order(), vec![0, 2, 3, 5]);
+	// growing puts the new slots first, followed by the rest
+	// in the same order as before
+	drop(controller);
+	arena.grow(2);
+	let controller = arena.controller();
+	assert_eq!(controller.free_list_order(), vec![6, 7, 0, 2, 3, 5]);
+	let reserved: Vec<usize> = std::iter::from_fn(|| controller.try_reserve().ok())
+		.map(|key| key.index)
+		.collect();
+	assert_eq!(reserved, vec![6, 7, 0, 2, 3, 5]);
+	drop(controller);
+	arena.insert_with_key(key4, 4).unwrap();
+	assert!(arena.debug_consistency());
+}
+
+#[test]
+fn lazy_controller_reserve_in_range() {
+	let arena = Arena::<()>::new_lazy(4);
+	let controller = arena.controller();
+	// a slot reserved in a range is skipped once the slots before
+	// it have been reserved
+	assert_eq!(controller.try_reserve_in_range(2..3).unwrap().index, 2);
+	assert_eq!(controller.free_list_order(), vec![0, 1, 3]);
+	let reserved: Vec<usize> = std::iter::from_fn(|| controller.try_reserve().ok())
+		.map(|key| key.index)
+		.collect();
+	assert_eq!(reserved, vec![0, 1, 3]);
+	assert_eq!(controller.len(), 4);
+	assert_eq!(controller.try_reserve_in_range(0..4), Err(ArenaFull));
+}
+
+#[test]
+fn controller_ref() {
+	let mut arena = Arena::new(2);
+	let key = arena.controller_ref().try_reserve().unwrap();
+	// the borrowed controller should share state with the arena
+	arena.insert_with_key(key, 1).unwrap();
+	assert_eq!(arena.controller_ref().len(), 1);
+	arena.insert(2).unwrap();
+	assert_eq!(arena.controller_ref().try_reserve(), Err(ArenaFull));
+}
+
+#[test]
+fn get_both_get_three() {
+	let mut positions = Arena::new(2);
+	let mut names = Arena::new(2);
+	let mut flags = Arena::new(2);
+	let position = positions.insert((0.0, 0.0)).unwrap();
+	let name = names.insert("a".to_string()).unwrap();
+	let flag = flags.insert(false).unwrap();
+	let (position_ref, name_ref, flag_ref) =
+		get_three(&mut positions, position, &mut names, name, &mut flags, flag).unwrap();
+	position_ref.0 = 1.0;
+	name_ref.push('b');
+	*flag_ref = true;
+	assert_eq!(positions.get(position), Some(&(1.0, 0.0)));
+	assert_eq!(names.get(name).map(String::as_str), Some("ab"));
+	assert_eq!(flags.get(flag), Some(&true));
+	let (name_ref, flag_ref) = get_both(&mut names, name, &mut flags, flag).unwrap();
+	name_ref.push('c');
+	*flag_ref = false;
+	assert_eq!(names.get(name).map(String::as_str), Some("abc"));
+	// if any key is dead, we should get `None`
+	flags.remove(flag);
+	assert!(get_both(&mut names, name, &mut flags, flag).is_none());
+	assert!(get_three(&mut positions, position, &mut names, name, &mut flags, flag).is_none());
+}
+
+#[test]
+fn compact_free_list() {
+	let mut arena = Arena::new(6);
+	let keys: Vec<_> = (0..6).map(|i| arena.insert(i).unwrap()).collect();
+	arena.remove(keys[1]);
+	arena.remove(keys[4]);
+	arena.remove(keys[2]);
+	arena.compact_free_list();
+	// the lowest slots should be reserved first
+	let indices: Vec<_> = (0..3).map(|i| arena.insert(i).unwrap().index).collect();
+	assert_eq!(indices, vec![1, 2, 4]);
+	// items should not be moved
+	assert_eq!(arena.get(keys[0]), Some(&0));
+	assert_eq!(arena.get(keys[5]), Some(&5));
+
+	let mut arena = Arena::with_reservation_order(6, ReservationOrder::Fifo);
+	let keys: Vec<_> = (0..6).map(|i| arena.insert(i).unwrap()).collect();
+	arena.remove(keys[4]);
+	arena.remove(keys[1]);
+	arena.compact_free_list();
+	let indices: Vec<_> = (0..2).map(|i| arena.insert(i).unwrap().index).collect();
+	assert_eq!(indices, vec![1, 4]);
+}
+
+#[test]
+fn auto_compact() {
+	let mut arena = Arena::new(8);
+	arena.set_auto_compact(2.0);
+	let keys: Vec<_> = (0..8).map(|i| arena.insert(i).unwrap()).collect();
+	// removing these items leaves the arena only slightly fragmented
+	arena.remove(keys[2]);
+	arena.remove(keys[3]);
+	arena.remove(keys[5]);
+	// so the most recently freed slot is reused first
+	let key = arena.insert(8).unwrap();
+	assert_eq!(key.index, 5);
+	arena.remove(key);
+	// removing these items leaves 3 items in the first 8 slots,
+	// which exceeds the threshold
+	arena.remove(keys[1]);
+	arena.remove(keys[6]);
+	// the threshold was just checked, so compaction only happens
+	// once there have been 4 more removals
+	for i in 9..11 {
+		let key = arena.insert(i).unwrap();
+		arena.remove(key);
+	}
+	let indices: Vec<_> = (0..3).map(|i| arena.insert(i).unwrap().index).collect();
+	assert_eq!(indices, vec![1, 2, 3]);
+}
+
+#[test]
+fn auto_compact_waits_between_compactions() {
+	let mut arena = Arena::new(8);
+	arena.set_auto_compact(2.0);
+	let keys: Vec<_> = (0..8).map(|i| arena.insert(i).unwrap()).collect();
+	for i in [0, 1, 2, 6] {
+		arena.remove(keys[i]);
+	}
+	// 4 items in 8 slots doesn't exceed the threshold
+	assert_eq!(arena.controller().free_list_order(), vec![6, 2, 1, 0]);
+	// 3 items in 8 slots does, but the threshold isn't checked
+	// again until there have been 4 more removals
+	for i in 8..10 {
+		let key = arena.insert(i).unwrap();
+		arena.remove(key);
+	}
+	arena.remove(keys[4]);
+	assert_eq!(arena.controller().free_list_order(), vec![4, 6, 2, 1, 0]);
+	// the 4th removal checks the threshold and compacts the free list
+	arena.remove(keys[3]);
+	assert_eq!(arena.controller().free_list_order(), vec![0, 1, 2, 3, 4, 6]);
+}
+
+#[test]
+fn auto_compact_for_every_removal_method() {
+	let removers: [fn(&mut Arena<usize>); 5] = [
+		|arena| arena.truncate_oldest(5),
+		|arena| {
+			let mut cursor = arena.cursor_mut();
+			while let Some((_, num)) = cursor.next() {
+				let remove = *num < 3;
+				if remove {
+					cursor.remove_current();
+				}
+			}
+		},
+		|arena| arena.retain(|num| *num >= 3),
+		|arena| arena.drain_filter(|num| *num < 3).for_each(drop),
+		|arena| {
+			arena.remove_where(|num| *num < 3);
+		},
+	];
+	for remove in removers {
+		let mut arena = Arena::new(8);
+		// the threshold is never exceeded, so the removals are
+		// only counted
+		arena.set_auto_compact(1000.0);
+		for i in 0..8 {
+			arena.insert(i).unwrap();
+		}
+		remove(&mut arena);
+		assert_eq!(arena.len(), 5);
+		assert_eq!(arena.removals_since_compaction, 3);
+	}
+}
+
+#[test]
+fn parts() {
+	let mut arena = Arena::new(6);
+	let keys: Vec<_> = (0..5).map(|i| arena.insert(i).unwrap()).collect();
+	arena.remove(keys[1]);
+	arena.remove(keys[3]);
+	let reserved_key = arena.controller().try_reserve().unwrap();
+	let parts = arena.into_parts();
+	assert_eq!(parts.slots.len(), 6);
+	assert_eq!(parts.free_list, vec![1, 5]);
+	assert_eq!(parts.occupied_list, vec![4, 2, 0]);
+	let mut arena = Arena::from_parts(parts).unwrap();
+	// all of the keys should still be valid
+	assert_eq!(arena.get(keys[0]), Some(&0));
+	assert_eq!(arena.get(keys[1]), None);
+	assert_eq!(arena.get(keys[2]), Some(&2));
+	assert_eq!(arena.get(keys[3]), None);
+	assert_eq!(arena.get(keys[4]), Some(&4));
+	assert_eq!(
+		arena.iter().map(|(key, _)| key).collect::<Vec<_>>(),
+		vec![keys[4], keys[2], keys[0]]
+	);
+	// the reserved key should still be reserved
+	assert!(arena.is_reserved(reserved_key));
+	arena.insert_with_key(reserved_key, 5).unwrap();
+	// the free list should be preserved
+	let key = arena.insert(6).unwrap();
+	assert!(key.is_same_slot(&keys[1]));
+	assert_eq!(key.generation, 1);
+	arena.insert(7).unwrap();
+	assert_eq!(arena.insert(8), Err(ArenaFull));
+	assert_eq!(arena.len(), 6);
+}
+
+#[test]
+fn invalid_parts() {
+	let mut arena = Arena::new(3);
+	let key = arena.insert(1).unwrap();
+	arena.insert(2).unwrap();
+	let parts = arena.into_parts();
+	// an occupied slot in the free list
+	let mut invalid_parts = parts.clone();
+	invalid_parts.free_list.push(key.index);
+	assert_eq!(
+		Arena::from_parts(invalid_parts).err(),
+		Some(InvalidArenaParts)
+	);
+	// a slot missing from the occupied list
+	let mut invalid_parts = parts.clone();
+	invalid_parts.occupied_list.pop();
+	assert_eq!(
+		Arena::from_parts(invalid_parts).err(),
+		Some(InvalidArenaParts)
+	);
+	// an out of bounds slot
+	let mut invalid_parts = parts.clone();
+	invalid_parts.free_list.push(3);
+	assert_eq!(
+		Arena::from_parts(invalid_parts).err(),
+		Some(InvalidArenaParts)
+	);
+	assert!(Arena::from_parts(parts).is_ok());
+	// lazily initialized slots should be included in the parts
+	let parts = Arena::<()>::new_lazy(2).into_parts();
+	assert_eq!(parts.slots.len(), 2);
+	assert_eq!(Arena::<()>::new_lazy(0).into_parts().slots.len(), 0);
+}
+
+#[test]
+fn min_max_by_key() {
+	let mut arena = Arena::new(5);
+	assert_eq!(arena.min_by_key(|num: &i32| *num), None);
+	let key1 = arena.insert(3).unwrap();
+	let key2 = arena.insert(-5).unwrap();
+	let key3 = arena.insert(4).unwrap();
+	let key4 = arena.insert(-4).unwrap();
+	let key5 = arena.insert(5).unwrap();
+	assert_eq!(arena.min_by_key(|num| *num), Some((key2, &-5)));
+	assert_eq!(arena.max_by_key(|num| *num), Some((key5, &5)));
+	// ties go to the first item in iteration order for the
+	// minimum and the last for the maximum (most recently added
+	// first)
+	assert_eq!(
+		arena.min_by_key(|num| (num.abs() + 1) / 2),
+		Some((key4, &-4))
+	);
+	assert_eq!(arena.max_by_key(|num| num.abs()), Some((key2, &-5)));
+	arena.remove(key5);
+	assert_eq!(arena.max_by_key(|num| num.abs()), Some((key2, &-5)));
+	assert_eq!(arena.min_by_key(|num| num.abs()), Some((key1, &3)));
+	arena.remove(key4);
+	assert_eq!(
+		arena.min_by_key(|num| (num.abs() + 1) / 2),
+		Some((key3, &4))
+	);
+}
+
+#[test]
+fn swap() {
+	let mut arena = Arena::new(3);
+	let key1 = arena.insert(1).unwrap();
+	let key2 = arena.insert(2).unwrap();
+	let key3 = arena.insert(3).unwrap();
+	assert_eq!(arena.swap(key1, key3), Ok(()));
+	assert_eq!(arena.get(key1), Some(&3));
+	assert_eq!(arena.get(key3), Some(&1));
+	// the iteration order of the slots shouldn't change
+	assert_eq!(
+		arena.iter().collect::<Vec<_>>(),
+		vec![(key3, &1), (key2, &2), (key1, &3)]
+	);
+	assert_eq!(arena.swap(key2, key2), Err(TwoMutError::SameSlot));
+	arena.remove(key2);
+	assert_eq!(arena.swap(key2, key1), Err(TwoMutError::FirstDead));
+	assert_eq!(arena.swap(key1, key2), Err(TwoMutError::SecondDead));
+}
+
+#[test]
+fn remove_where_ordered() {
+	let mut arena = Arena::new(6);
+	let keys = (0..6).map(|i| arena.insert(i).unwrap()).collect::<Vec<_>>();
+	arena.remove(keys[2]);
+	let key = arena.insert(10).unwrap();
+	// the items should come out in slot order, not iteration order
+	assert_eq!(
+		arena.remove_where_ordered(|num| num % 2 == 0),
+		vec![(0, keys[0], 0), (2, key, 10), (4, keys[4], 4)]
+	);
+	assert_eq!(
+		arena.iter().collect::<Vec<_>>(),
+		vec![(keys[5], &5), (keys[3], &3), (keys[1], &1)]
+	);
+	assert_eq!(arena.remove_where_ordered(|_| false), vec![]);
+}
+
+#[test]
+fn invariants_hold_after_mutations() {
+	// check_invariants runs after every mutation in tests,
+	// so this just needs to exercise the mutating operations
+	let mut arena = Arena::new(10);
+	let keys = (0..8).map(|i| arena.insert(i).unwrap()).collect::<Vec<_>>();
+	arena.remove(keys[3]);
+	arena.update(keys[4], |num| num * 2).unwrap();
+	arena.rotate_occupied(3);
+	arena.retire(keys[5]);
+	arena.retain(|num| num % 3 != 0);
+	arena.drain_filter(|num| *num == 1).for_each(drop);
+	arena.truncate_oldest(2);
+	assert_eq!(arena.len(), 2);
+	assert_eq!(arena.len(), arena.iter().count());
+	let mut filled = Arena::filled(4, 0);
+	filled.remove_where_ordered(|_| true);
+	assert_eq!(filled.len(), 0);
+}
+
+#[test]
+fn new_with_generation_seed() {
+	let mut arena = Arena::new_with_generation_seed(3, |index| usize::MAX - 1 - index);
+	let key = arena.insert(1).unwrap();
+	assert_eq!(key.index, 0);
+	assert_eq!(key.generation, usize::MAX - 1);
+	let key2 = arena.insert(2).unwrap();
+	assert_eq!(key2.generation, usize::MAX - 2);
+	assert_eq!(arena.remove(key), Some(1));
+	// the slot's generation should keep increasing from the seed
+	let key3 = arena.insert(3).unwrap();
+	assert_eq!(key3.index, 0);
+	assert_eq!(key3.generation, usize::MAX);
+	assert_eq!(arena.get(key), None);
+	assert_eq!(arena.get(key3), Some(&3));
+}
+
+#[test]
+fn remove_and_reserve() {
+	let mut arena = Arena::with_reservation_order(3, ReservationOrder::Fifo);
+	let key1 = arena.insert(1).unwrap();
+	let key2 = arena.insert(2).unwrap();
+	arena.remove(key2);
+	// with fifo order, a normal reservation would use slot 2
+	let new_key = arena.remove_and_reserve(key1).unwrap();
+	assert_eq!(new_key.index, key1.index);
+	assert_eq!(new_key.generation, key1.generation + 1);
+	assert!(!arena.contains(key1));
+	assert!(arena.is_reserved(new_key));
+	assert_eq!(arena.controller().len(), 1);
+	assert_eq!(arena.insert_with_key(new_key, 3), Ok(()));
+	assert_eq!(arena.get(new_key), Some(&3));
+	assert_eq!(arena.remove_and_reserve(key1), None);
+	assert_eq!(arena.remove_and_reserve(key2), None);
+}
+
+#[test]
+fn chunks() {
+	let mut arena = Arena::new(7);
+	for i in 0..7 {
+		arena.insert(i).unwrap();
+	}
+	let chunks = arena
+		.chunks(3)
+		.map(|chunk| chunk.into_iter().map(|(_, num)| *num).collect::<Vec<_>>())
+		.collect::<Vec<_>>();
+	assert_eq!(chunks, vec![vec![6, 5, 4], vec![3, 2, 1], vec![0]]);
+	assert_eq!(Arena::<i32>::new(3).chunks(3).count(), 0);
+}
+
+#[test]
+#[should_panic]
+fn chunks_of_zero_panics() {
+	let arena = Arena::<i32>::new(3);
+	let _ = arena.chunks(0);
+}
+
+#[test]
+fn fold() {
+	let mut arena = Arena::new(5);
+	for i in 0..4 {
+		arena.insert(i * 10).unwrap();
+	}
+	assert_eq!(arena.fold(0, |sum, key, num| sum + key.index + num), 66);
+	assert_eq!(
+		arena.fold(vec![], |mut keys, key, _| {
+			keys.push(key);
+			keys
+		}),
+		arena.iter().map(|(key, _)| key).collect::<Vec<_>>()
+	);
+}
+
+#[test]
+fn typed_keys() {
+	let mut arena = Arena::new(2);
+	let key = arena.insert_typed(1).unwrap();
+	assert!(arena.contains_typed(key));
+	assert_eq!(arena.get_typed(key), Some(&1));
+	*arena.get_typed_mut(key).unwrap() = 2;
+	assert_eq!(arena.get(key.key()), Some(&2));
+	// typed keys can be converted to and from untyped keys
+	let untyped: Key = key.into();
+	assert_eq!(TypedKey::<i32>::new(untyped), key);
+	assert_eq!(arena.remove_typed(key), Some(2));
+	assert!(!arena.contains_typed(key));
+	assert_eq!(arena.get_typed(key), None);
+}
+
+#[test]
+fn contention_stats() {
+	const THREADS: usize = 8;
+	const RESERVATIONS_PER_THREAD: usize = 50_000;
+	const MAX_ROUNDS: usize = 200;
+	let mut arena = Arena::<()>::new(1 + THREADS * RESERVATIONS_PER_THREAD);
+	arena.insert(()).unwrap();
+	let controller = arena.controller();
+	assert_eq!(
+		controller.contention_stats(),
+		ContentionStats {
+			successful_reservations: 1,
+			reservation_retries: 0,
+		}
+	);
+	// keep hammering the free list from multiple threads until
+	// at least one reservation has to retry
+	let mut rounds = 0;
+	while controller.contention_stats().reservation_retries == 0 && rounds < MAX_ROUNDS {
+		let barrier = std::sync::Barrier::new(THREADS);
+		let keys: Vec<Key> = std::thread::scope(|scope| {
+			let handles: Vec<_> = (0..THREADS)
+				.map(|_| {
+					let controller = controller.clone();
+					let barrier = &barrier;
+					scope.spawn(move || {
+						barrier.wait();
+						(0..RESERVATIONS_PER_THREAD)
+							.map(|_| controller.try_reserve().unwrap())
+							.collect::<Vec<_>>()
+					})
+				})
+				.collect();
+			handles
+				.into_iter()
+				.flat_map(|handle| handle.join().unwrap())
+				.collect()
+		});
+		// free the slots again so the next round has room
+		for key in keys {
+			controller.free(key.index);
+		}
+		rounds += 1;
+	}
+	let stats = controller.contention_stats();
+	assert!(stats.reservation_retries > 0);
+	assert_eq!(
+		stats.successful_reservations,
+		1 + rounds * THREADS * RESERVATIONS_PER_THREAD
+	);
+}
+
+#[test]
+fn force_free() {
+	let mut arena = Arena::new(3);
+	let key1 = arena.insert(1).unwrap();
+	let key2 = arena.insert(2).unwrap();
+	assert_eq!(arena.force_free(key1.index), Some(1));
+	assert!(!arena.contains(key1));
+	assert_eq!(arena.get(key2), Some(&2));
+	assert_eq!(arena.iter().collect::<Vec<_>>(), vec![(key2, &2)]);
+	// the slot should be free to use again
+	let key3 = arena.insert(3).unwrap();
+	assert_eq!(key3.index, key1.index);
+	assert!(!arena.contains(key1));
+	assert_eq!(arena.force_free(2), None);
+	assert_eq!(arena.force_free(10), None);
+}
+
+#[test]
+fn read_version() {
+	let mut arena = Arena::new(1);
+	let key = arena.insert(1).unwrap();
+	let version = arena.read_version(key);
+	assert_eq!(version, Some(key.generation));
+	arena.remove(key);
+	assert_eq!(arena.read_version(key), None);
+	let new_key = arena.insert(2).unwrap();
+	assert_eq!(new_key.index, key.index);
+	assert_ne!(arena.read_version(new_key), version);
+	assert_eq!(arena.read_version(key), None);
+}
+
+#[test]
+fn debug_consistency() {
+	let mut arena = Arena::new(5);
+	assert!(arena.debug_consistency());
+	let key = arena.insert(1).unwrap();
+	arena.insert(2).unwrap();
+	let controller = arena.controller();
+	assert_eq!(controller.free_count(), 3);
+	// reserved slots are counted separately from free slots and items
+	let reserved_key = controller.try_reserve().unwrap();
+	assert_eq!(controller.free_count(), 2);
+	assert_eq!(arena.len(), 2);
+	assert!(arena.debug_consistency());
+	arena.retire(reserved_key);
+	arena.remove(key);
+	assert!(arena.debug_consistency());
+	// an occupied slot that's marked as free breaks the accounting
+	let key = arena.insert(3).unwrap();
+	controller.free(key.index);
+	assert!(!arena.debug_consistency());
+	assert!(Arena::<()>::new_lazy(3).debug_consistency());
+}
+
+#[test]
+fn reset_free_slot_generations() {
+	let mut arena = Arena::new(4);
+	let keys = (0..4).map(|i| arena.insert(i).unwrap()).collect::<Vec<_>>();
+	for key in &keys {
+		arena.remove(key);
+	}
+	let occupied_key = arena.insert(10).unwrap();
+	let reserved_key = arena.controller().try_reserve().unwrap();
+	arena.reset_free_slot_generations();
+	// occupied and reserved slots keep their generations
+	assert_eq!(arena.get(occupied_key), Some(&10));
+	assert!(arena.is_reserved(reserved_key));
+	for index in 0..4 {
+		let debug = arena.debug_slot(index).unwrap();
+		if index == occupied_key.index || index == reserved_key.index {
+			assert_eq!(debug.generation(), 1);
+		} else {
+			assert_eq!(debug.generation(), 0);
+		}
+	}
+	// the remaining free slots should still be reserved in the
+	// same order as before
+	let new_keys = (0..2).map(|_| arena.insert(0).unwrap()).collect::<Vec<_>>();
+	assert_eq!(new_keys[0].index, keys[1].index);
+	assert_eq!(new_keys[1].index, keys[0].index);
+	assert!(new_keys.iter().all(|key| key.generation == 0));
+}
+
+#[test]
+fn iter_except() {
+	let mut arena = Arena::new(3);
+	let key1 = arena.insert(1).unwrap();
+	let key2 = arena.insert(2).unwrap();
+	let key3 = arena.insert(3).unwrap();
+	let mut exclude = HashSet::new();
+	exclude.insert(key2);
+	assert_eq!(
+		arena.iter_except(&exclude).collect::<Vec<_>>(),
+		vec![(key3, &3), (key1, &1)]
+	);
+	// a stale key shouldn't hide the new item in its slot
+	arena.remove(key2);
+	let key4 = arena.insert(4).unwrap();
+	assert_eq!(key4.index, key2.index);
+	assert_eq!(
+		arena.iter_except(&exclude).collect::<Vec<_>>(),
+		vec![(key4, &4), (key3, &3), (key1, &1)]
+	);
+}
+
+#[test]
+fn apply_updates() {
+	let mut arena = Arena::new(3);
+	let key1 = arena.insert(1).unwrap();
+	let key2 = arena.insert(2).unwrap();
+	let key3 = arena.insert(3).unwrap();
+	arena.remove(key2);
+	let reserved_key = arena.controller().try_reserve().unwrap();
+	let mut updates = HashMap::new();
+	updates.insert(key1, 10);
+	updates.insert(key2, 20);
+	updates.insert(key3, 30);
+	updates.insert(reserved_key, 40);
+	let stale_keys = arena.apply_updates(updates);
+	assert_eq!(
+		stale_keys.into_iter().collect::<HashSet<_>>(),
+		vec![key2, reserved_key].into_iter().collect::<HashSet<_>>()
+	);
+	assert_eq!(
+		arena.iter().collect::<Vec<_>>(),
+		vec![(key3, &30), (key1, &10)]
+	);
+}
+
+#[test]
+fn relocate() {
+	let mut arena = Arena::new(4);
+	let key1 = arena.insert(1).unwrap();
+	let key2 = arena.insert(2).unwrap();
+	let new_key = arena.relocate(key1, 3).unwrap();
+	assert_eq!(new_key.index, 3);
+	assert!(!arena.contains(key1));
+	assert_eq!(arena.get(new_key), Some(&1));
+	assert_eq!(
+		arena.iter().collect::<Vec<_>>(),
+		vec![(new_key, &1), (key2, &2)]
+	);
+	// the old slot should be free again
+	let key3 = arena.insert(3).unwrap();
+	assert_eq!(key3.index, key1.index);
+	assert_ne!(key3, key1);
+	// occupied, reserved, and out of bounds slots can't be targeted
+	assert_eq!(arena.relocate(key2, key3.index), None);
+	let reserved_key = arena.controller().try_reserve().unwrap();
+	assert_eq!(arena.relocate(key2, reserved_key.index), None);
+	assert_eq!(arena.relocate(key2, 4), None);
+	assert_eq!(arena.relocate(key1, 2), None);
+	assert_eq!(arena.get(key2), Some(&2));
+}
+
+#[test]
+fn free_fragmentation() {
+	let mut arena = Arena::new(8);
+	assert_eq!(
+		arena.free_fragmentation(),
+		FragmentationStats {
+			free_runs: 1,
+			largest_free_run: 8,
+		}
+	);
+	let keys = (0..8).map(|i| arena.insert(i).unwrap()).collect::<Vec<_>>();
+	assert_eq!(
+		arena.free_fragmentation(),
+		FragmentationStats {
+			free_runs: 0,
+			largest_free_run: 0,
+		}
+	);
+	// free slots: 0, 2, 3, 5, 6, 7
+	for index in [0, 2, 3, 5, 6, 7] {
+		arena.remove(keys[index]);
+	}
+	assert_eq!(
+		arena.free_fragmentation(),
+		FragmentationStats {
+			free_runs: 3,
+			largest_free_run: 3,
+		}
+	);
+	// reserved slots don't count as free
+	arena.controller().try_reserve_in_range(6..7).unwrap();
+	assert_eq!(
+		arena.free_fragmentation(),
+		FragmentationStats {
+			free_runs: 4,
+			largest_free_run: 2,
+		}
+	);
+}
+
+#[test]
+fn access() {
+	let mut arena = Arena::new(3);
+	let key = arena.insert(1).unwrap();
+	assert_eq!(arena.access(key), Ok(&1));
+	*arena.access_mut(key).unwrap() = 2;
+	assert_eq!(arena.access(key), Ok(&2));
+	let reserved_key = arena.controller().try_reserve().unwrap();
+	assert_eq!(arena.access(reserved_key), Err(AccessError::Freed));
+	assert_eq!(arena.try_remove(key), Ok(2));
+	assert_eq!(arena.access(key), Err(AccessError::StaleGeneration));
+	assert_eq!(arena.access_mut(key), Err(AccessError::StaleGeneration));
+	assert_eq!(arena.try_remove(key), Err(AccessError::StaleGeneration));
+	let out_of_bounds_key = Key {
+		index: 3,
+		generation: 0,
+	};
+	assert_eq!(
+		arena.access(out_of_bounds_key),
+		Err(AccessError::OutOfBounds)
+	);
+	// uninitialized slots in lazy arenas are treated as free
+	let arena = Arena::<i32>::new_lazy(3);
+	assert_eq!(
+		arena.access(Key {
+			index: 2,
+			generation: 0
+		}),
+		Err(AccessError::Freed)
+	);
+	assert_eq!(
+		arena.access(Key {
+			index: 2,
+			generation: 1
+		}),
+		Err(AccessError::StaleGeneration)
+	);
+}
+
+#[test]
+fn find() {
+	let mut arena = Arena::new(4);
+	arena.insert(1).unwrap();
+	let key2 = arena.insert(2).unwrap();
+	arena.insert(3).unwrap();
+	let key4 = arena.insert(4).unwrap();
+	assert_eq!(arena.find(|num| num % 2 == 0), Some((key4, &4)));
+	let (key, num) = arena.find(|num| *num < 3 && *num > 1).unwrap();
+	assert_eq!(key, key2);
+	assert!(std::ptr::eq(num, arena.get(key2).unwrap()));
+	assert_eq!(arena.find(|num| *num > 4), None);
+}
+
+#[test]
+fn used() {
+	let mut arena = Arena::new(4);
+	assert_eq!(arena.used(), 0);
+	arena.insert(1).unwrap();
+	assert_eq!(arena.used(), 1);
+	let reserved_key = arena.controller().try_reserve().unwrap();
+	assert_eq!(arena.used(), 2);
+	assert!(arena.used() > arena.len());
+	arena.insert_with_key(reserved_key, 2).unwrap();
+	assert_eq!(arena.used(), arena.len());
+}
+
+#[test]
+fn reserved_lane() {
+	let mut arena = Arena::new_with_reserved_lane(4, 2);
+	let controller = arena.controller();
+	// normal reservations can only use the first 2 slots
+	let key1 = arena.insert(1).unwrap();
+	let key2 = arena.insert(2).unwrap();
+	assert_eq!(arena.insert(3), Err(ArenaFull));
+	assert_eq!(controller.try_reserve(), Err(ArenaFull));
+	let priority_key = controller.try_reserve_priority().unwrap();
+	assert!(priority_key.index >= 2);
+	arena.insert_with_key(priority_key, 3).unwrap();
+	// priority reservations use normal slots first
+	arena.remove(key1);
+	let priority_key2 = controller.try_reserve_priority().unwrap();
+	assert_eq!(priority_key2.index, key1.index);
+	let priority_key3 = controller.try_reserve_priority().unwrap();
+	assert!(priority_key3.index >= 2);
+	assert_eq!(controller.try_reserve_priority(), Err(ArenaFull));
+	// freed priority slots go back to the priority lane
+	arena.remove(priority_key);
+	assert_eq!(controller.try_reserve(), Err(ArenaFull));
+	assert_eq!(
+		controller.try_reserve_priority().unwrap().index,
+		priority_key.index
+	);
+	// compacting shouldn't move slots between the lanes
+	arena.remove(key2);
+	arena.compact_free_list();
+	assert_eq!(controller.try_reserve().unwrap().index, key2.index);
+	assert_eq!(controller.try_reserve(), Err(ArenaFull));
+}
+
+#[test]
+fn reserved_lane_without_normal_slots() {
+	let arena = Arena::<()>::new_with_reserved_lane(2, 2);
+	let controller = arena.controller();
+	assert_eq!(controller.try_reserve(), Err(ArenaFull));
+	assert!(controller.try_reserve_priority().is_ok());
+	assert!(controller.try_reserve_priority().is_ok());
+	assert_eq!(controller.try_reserve_priority(), Err(ArenaFull));
+}
+
+#[test]
+fn eq_unordered() {
+	let mut arena1 = Arena::new(4);
+	arena1.insert(1).unwrap();
+	arena1.insert(2).unwrap();
+	arena1.insert(2).unwrap();
+	let mut arena2 = Arena::new(5);
+	let key = arena2.insert(0).unwrap();
+	arena2.insert(2).unwrap();
+	arena2.insert(1).unwrap();
+	arena2.insert(2).unwrap();
+	arena2.remove(key);
+	assert!(arena1.eq_unordered(&arena2));
+	assert!(arena2.eq_unordered(&arena1));
+	// the iteration order is different
+	assert_ne!(
+		arena1.iter().map(|(_, num)| *num).collect::<Vec<_>>(),
+		arena2.iter().map(|(_, num)| *num).collect::<Vec<_>>()
+	);
+	// duplicates need to match up
+	let mut arena3 = Arena::new(3);
+	arena3.insert(1).unwrap();
+	arena3.insert(1).unwrap();
+	arena3.insert(2).unwrap();
+	assert!(!arena1.eq_unordered(&arena3));
+	assert!(!arena1.eq_unordered(&Arena::new(0)));
+}
+
+#[test]
+fn to_dense() {
+	let mut arena = Arena::new(5);
+	let keys = (0..5)
+		.map(|i| arena.insert(i * 10).unwrap())
+		.collect::<Vec<_>>();
+	arena.remove(keys[1]);
+	arena.remove(keys[3]);
+	let (dense, positions) = arena.to_dense();
+	assert_eq!(dense.len(), arena.len());
+	assert_eq!(dense, vec![0, 20, 40]);
+	assert_eq!(positions.len(), arena.len());
+	for (key, num) in arena.iter() {
+		assert_eq!(dense[positions[&key]], *num);
+	}
+	assert!(!positions.contains_key(&keys[1]));
+}
+
+#[test]
+fn from_dense_updates() {
+	let mut arena = Arena::new(4);
+	let keys = (0..4).map(|i| arena.insert(i).unwrap()).collect::<Vec<_>>();
+	let (mut dense, positions) = arena.to_dense();
+	for num in &mut dense {
+		*num *= 10;
+	}
+	// stale keys should be ignored
+	arena.remove(keys[2]);
+	let new_key = arena.insert(100).unwrap();
+	arena.from_dense_updates(&dense, &positions);
+	assert_eq!(arena.get(keys[0]), Some(&0));
+	assert_eq!(arena.get(keys[1]), Some(&10));
+	assert_eq!(arena.get(keys[3]), Some(&30));
+	assert_eq!(arena.get(new_key), Some(&100));
+}
+
+#[test]
+fn try_new() {
+	let mut arena = Arena::try_new(3).unwrap();
+	assert_eq!(arena.capacity(), 3);
+	arena.insert(1).unwrap();
+	assert_eq!(Arena::<i32>::try_new(usize::MAX).err(), Some(CapacityError));
+	assert_eq!(
+		Arena::<i32>::try_new(usize::MAX / 2 + 1).err(),
+		Some(CapacityError)
+	);
+	assert_eq!(
+		Arena::<[u8; 1024]>::try_new_with_memory_limit(1024, 1024 * 1024).err(),
+		Some(CapacityError)
+	);
+	assert!(Arena::<[u8; 1024]>::try_new_with_memory_limit(10, 1024 * 1024).is_ok());
+	// running out of memory for the controller's slots shouldn't
+	// abort either
+	let controller_bytes = 1000 * Controller::memory_per_slot();
+	assert_ne!(controller_bytes, 1000 * std::mem::size_of::<ArenaSlot>());
+	assert_ne!(controller_bytes, 1000 * std::mem::size_of::<i32>());
+	assert_eq!(
+		with_failing_allocation_size(controller_bytes, || Arena::<i32>::try_new(1000)).err(),
+		Some(CapacityError)
+	);
+	assert!(Arena::<i32>::try_new(1000).is_ok());
+}
+
+#[test]
+fn generations_wrap_around() {
+	let mut arena = Arena::new_with_generation_seed(1, |_| usize::MAX);
+	let key = arena.insert(1).unwrap();
+	assert_eq!(key.generation, usize::MAX);
+	assert_eq!(arena.remove(key), Some(1));
+	let key2 = arena.insert(2).unwrap();
+	assert_eq!(key2.generation, 0);
+	assert_eq!(arena.get(key), None);
+	assert_eq!(arena.get(key2), Some(&2));
+	// retiring a reserved key should wrap the same way
+	let mut arena = Arena::<()>::new_with_generation_seed(1, |_| usize::MAX);
+	let key = arena.controller().try_reserve().unwrap();
+	arena.retire(key);
+	assert_eq!(arena.debug_slot(0).unwrap().generation(), 0);
+}
+
+#[test]
+fn free_list_order() {
+	let mut arena = Arena::new(4);
+	let controller = arena.controller();
+	assert_eq!(controller.free_list_order(), vec![0, 1, 2, 3]);
+	let key1 = arena.insert(1).unwrap();
+	let key2 = arena.insert(2).unwrap();
+	assert_eq!(controller.free_list_order(), vec![2, 3]);
+	arena.remove(key1);
+	arena.remove(key2);
+	assert_eq!(controller.free_list_order(), vec![1, 0, 2, 3]);
+	let mut arena = Arena::with_reservation_order(4, ReservationOrder::Fifo);
+	let key1 = arena.insert(1).unwrap();
+	let key2 = arena.insert(2).unwrap();
+	arena.remove(key1);
+	arena.remove(key2);
+	assert_eq!(arena.controller().free_list_order(), vec![2, 3, 0, 1]);
+	let arena = Arena::<()>::new_with_reserved_lane(4, 1);
+	assert_eq!(arena.controller().free_list_order(), vec![0, 1, 2, 3]);
+	assert_eq!(Arena::<()>::new(0).controller().free_list_order(), vec![]);
+}
+
+#[test]
+fn single_thread_insert_and_get() {
+	let mut arena = SingleThreadArena::new(3);
+	assert!(arena.is_empty());
+	let key1 = arena.insert(1).unwrap();
+	let key2 = arena.insert(2).unwrap();
+	assert_eq!(arena.len(), 2);
+	assert_eq!(arena.get(key1), Some(&1));
+	assert_eq!(arena.get(key2), Some(&2));
+	*arena.get_mut(key1).unwrap() = 10;
+	assert_eq!(arena.get(key1), Some(&10));
+	assert!(arena.contains(key2));
+}
+
+#[test]
+fn single_thread_capacity() {
+	let mut arena = SingleThreadArena::new(2);
+	assert_eq!(arena.capacity(), 2);
+	arena.insert(1).unwrap();
+	arena.insert(2).unwrap();
+	assert_eq!(arena.insert(3), Err(ArenaFull));
+	assert_eq!(SingleThreadArena::new(0).insert(()), Err(ArenaFull));
+}
+
+#[test]
+fn single_thread_remove() {
+	let mut arena = SingleThreadArena::new(2);
+	let key1 = arena.insert(1).unwrap();
+	let key2 = arena.insert(2).unwrap();
+	assert_eq!(arena.remove(key1), Some(1));
+	assert_eq!(arena.remove(key1), None);
+	assert_eq!(arena.get(key1), None);
+	assert_eq!(arena.len(), 1);
+	// the freed slot should be reused with a new generation
+	let key3 = arena.insert(3).unwrap();
+	assert_eq!(key3.index, key1.index);
+	assert_ne!(key3, key1);
+	assert_eq!(arena.get(key1), None);
+	assert_eq!(arena.get(key3), Some(&3));
+	assert_eq!(arena.get(key2), Some(&2));
+	assert_eq!(arena.insert(4), Err(ArenaFull));
+}
+
+#[test]
+fn single_thread_iter() {
+	let mut arena = SingleThreadArena::new(4);
+	let key1 = arena.insert(1).unwrap();
+	let key2 = arena.insert(2).unwrap();
+	let key3 = arena.insert(3).unwrap();
+	arena.remove(key2);
+	assert_eq!(
+		arena.iter().collect::<Vec<_>>(),
+		vec![(key1, &1), (key3, &3)]
+	);
+	for (_, num) in arena.iter_mut() {
+		*num *= 10;
+	}
+	assert_eq!(
+		arena.iter().collect::<Vec<_>>(),
+		vec![(key1, &10), (key3, &30)]
+	);
+}
+
+#[test]
+fn into_indexed_vec_and_from_indexed() {
+	let mut arena = Arena::new(5);
+	let keys = (0..5).map(|i| arena.insert(i).unwrap()).collect::<Vec<_>>();
+	arena.remove(keys[1]);
+	arena.remove(keys[3]);
+	let key = arena.insert(10).unwrap();
+	let items = arena.into_indexed_vec();
+	assert_eq!(
+		items,
+		vec![(keys[0], 0), (keys[2], 2), (key, 10), (keys[4], 4)]
+	);
+	let mut arena = Arena::from_indexed(5, items).unwrap();
+	assert_eq!(arena.capacity(), 5);
+	assert_eq!(arena.len(), 4);
+	assert_eq!(arena.get(keys[0]), Some(&0));
+	assert_eq!(arena.get(keys[2]), Some(&2));
+	assert_eq!(arena.get(key), Some(&10));
+	assert_eq!(arena.get(keys[4]), Some(&4));
+	assert_eq!(
+		arena.iter().map(|(_, num)| *num).collect::<Vec<_>>(),
+		vec![4, 10, 2, 0]
+	);
+	// the remaining free slot should still be usable
+	let new_key = arena.insert(20).unwrap();
+	assert_eq!(new_key.index, keys[1].index);
+	assert_eq!(arena.insert(30), Err(ArenaFull));
+	assert_eq!(
+		Arena::from_indexed(2, vec![(keys[0], 0), (keys[0], 1)]).err(),
+		Some(InvalidArenaParts)
+	);
+	assert_eq!(
+		Arena::from_indexed(2, vec![(keys[4], 0)]).err(),
+		Some(InvalidArenaParts)
+	);
+}
+
+#[test]
+fn prefetch() {
+	let mut arena = Arena::new(2);
+	let key = arena.insert(1).unwrap();
+	arena.prefetch(key);
+	arena.remove(key);
+	arena.prefetch(key);
+	arena.prefetch(Key {
+		index: 100,
+		generation: 0,
+	});
+	Arena::<()>::new_lazy(10).prefetch(Key {
+		index: 5,
+		generation: 0,
+	});
+}
+
+#[test]
+fn split_off() {
+	let mut arena = Arena::new(6);
+	let keys = (0..6).map(|i| arena.insert(i).unwrap()).collect::<Vec<_>>();
+	let mut odd = arena.split_off(|num| num % 2 == 1);
+	assert_eq!(odd.capacity(), 6);
+	assert_eq!(arena.len(), 3);
+	assert_eq!(odd.len(), 3);
+	// each key should be valid for exactly one of the arenas
+	for (i, key) in keys.iter().enumerate() {
+		if i % 2 == 1 {
+			assert_eq!(odd.get(key), Some(&i));
+			assert_eq!(arena.get(key), None);
+		} else {
+			assert_eq!(arena.get(key), Some(&i));
+			assert_eq!(odd.get(key), None);
+		}
+	}
+	// the iteration order of the split off items should be kept
+	assert_eq!(
+		odd.iter().map(|(_, num)| *num).collect::<Vec<_>>(),
+		vec![5, 3, 1]
+	);
+	// inserting new items doesn't make the old keys valid for
+	// the other arena
+	for i in 10..13 {
+		odd.insert(i).unwrap();
+		arena.insert(i).unwrap();
+	}
+	for (i, key) in keys.iter().enumerate() {
+		if i % 2 == 1 {
+			assert_eq!(arena.get(key), None);
+		} else {
+			assert_eq!(odd.get(key), None);
+		}
+	}
+	// but keys for new items can be valid for both arenas
+	let mut arena = Arena::new(1);
+	let key = arena.insert(0).unwrap();
+	let mut other = arena.split_off(|_| false);
+	arena.remove(key);
+	let key1 = arena.insert(1).unwrap();
+	let key2 = other.insert(2).unwrap();
+	assert_eq!(key1, key2);
+	assert_eq!(arena.get(key2), Some(&1));
+	assert_eq!(other.get(key1), Some(&2));
+}
+
+#[test]
+fn as_contiguous_slice() {
+	// empty arenas are trivially packed
+	assert_eq!(Arena::<i32>::new(3).as_contiguous_slice(), Some(&[][..]));
+	assert_eq!(
+		Arena::filled(3, 1).as_contiguous_slice(),
+		Some(&[1, 1, 1][..])
+	);
+	let mut arena = Arena::new(4);
+	let keys = (0..3).map(|i| arena.insert(i).unwrap()).collect::<Vec<_>>();
+	assert_eq!(arena.as_contiguous_slice(), Some(&[0, 1, 2][..]));
+	// removing an item from the middle leaves a hole
+	arena.remove(keys[1]);
+	assert_eq!(arena.as_contiguous_slice(), None);
+	// removing the last item fills the hole back in
+	arena.remove(keys[2]);
+	assert_eq!(arena.as_contiguous_slice(), Some(&[0][..]));
+}
+
+#[test]
+fn drops_items_exactly_once() {
+	let item = Rc::new(());
+	let mut arena = Arena::new(6);
+	let keys = (0..5)
+		.map(|_| arena.insert(item.clone()).unwrap())
+		.collect::<Vec<_>>();
+	assert_eq!(Rc::strong_count(&item), 6);
+	// removed items are handed back rather than dropped
+	let removed = arena.remove(keys[0]).unwrap();
+	assert_eq!(Rc::strong_count(&item), 6);
+	drop(removed);
+	assert_eq!(Rc::strong_count(&item), 5);
+	// overwriting an item drops the old one
+	*arena.get_mut(keys[1]).unwrap() = Rc::new(());
+	assert_eq!(Rc::strong_count(&item), 4);
+	// dropping the arena drops the remaining items
+	drop(arena);
+	assert_eq!(Rc::strong_count(&item), 1);
+
+	// items that aren't consumed by into_values are dropped
+	// along with the iterator
+	let mut arena = Arena::new(4);
+	for _ in 0..4 {
+		arena.insert(item.clone()).unwrap();
+	}
+	let mut values = arena.into_values();
+	let value = values.next().unwrap();
+	drop(values);
+	assert_eq!(Rc::strong_count(&item), 2);
+	drop(value);
+
+	// items moved out by into_indexed_vec aren't dropped twice
+	let mut arena = Arena::new_lazy(4);
+	for _ in 0..3 {
+		arena.insert(item.clone()).unwrap();
+	}
+	let items = arena.into_indexed_vec();
+	assert_eq!(Rc::strong_count(&item), 4);
+	drop(items);
+	assert_eq!(Rc::strong_count(&item), 1);
+}
+
+#[test]
+fn items_stay_with_their_keys() {
+	let mut arena = Arena::new(8);
+	let mut expected = HashMap::new();
+	for i in 0..8 {
+		expected.insert(arena.insert(i).unwrap(), i);
+	}
+	let keys = arena.iter().map(|(key, _)| key).collect::<Vec<_>>();
+	for key in [keys[1], keys[4], keys[6]] {
+		assert_eq!(arena.remove(key), expected.remove(&key));
+	}
+	arena.swap(keys[0], keys[2]).unwrap();
+	expected.insert(keys[0], 5);
+	expected.insert(keys[2], 7);
+	let relocated = arena.relocate(keys[3], 1).unwrap();
+	expected.insert(relocated, expected[&keys[3]]);
+	expected.remove(&keys[3]);
+	arena.rotate_occupied(2);
+	for value in arena.iter_mut().map(|(_, value)| value) {
+		*value *= 10;
+	}
+	for value in expected.values_mut() {
+		*value *= 10;
+	}
+	assert_eq!(arena.len(), expected.len());
+	for (key, value) in &expected {
+		assert_eq!(arena.get(key), Some(value));
+	}
+	// the different ways of iterating should agree
+	let by_iter = arena
+		.iter()
+		.map(|(key, value)| (key, *value))
+		.collect::<HashMap<_, _>>();
+	let by_iter_ordered = arena
+		.iter_ordered()
+		.map(|(key, value)| (key, *value))
+		.collect::<HashMap<_, _>>();
+	assert_eq!(by_iter, expected);
+	assert_eq!(by_iter_ordered, expected);
+	assert_eq!(
+		arena
+			.iter_ordered()
+			.map(|(key, _)| key.index)
+			.collect::<Vec<_>>(),
+		{
+			let mut indices = expected.keys().map(|key| key.index).collect::<Vec<_>>();
+			indices.sort_unstable();
+			indices
+		}
+	);
+}
+
+#[test]
+fn set_slot_generation() {
+	let mut arena = Arena::new(2);
+	let old_key = arena.insert(1).unwrap();
+	arena.remove(old_key);
+	arena.set_slot_generation(old_key.index, usize::MAX - 1);
+	assert_eq!(
+		arena.debug_slot(old_key.index).unwrap().generation(),
+		usize::MAX - 1
+	);
+	// the controller should hand out keys with the new generation
+	let key = arena.insert(2).unwrap();
+	assert!(key.is_same_slot(&old_key));
+	assert_eq!(key.generation, usize::MAX - 1);
+	arena.remove(key);
+	let key = arena.insert(3).unwrap();
+	assert_eq!(key.generation, usize::MAX);
+	// generations wrap around instead of saturating or retiring
+	// the slot, so a key from generation 0 becomes valid again
+	arena.remove(key);
+	let key = arena.insert(4).unwrap();
+	assert_eq!(key.generation, 0);
+	assert_eq!(arena.get(old_key), Some(&4));
+	// uninitialized slots can be set as well
+	let mut arena = Arena::new_lazy(2);
+	arena.set_slot_generation(1, usize::MAX);
+	arena.insert(1).unwrap();
+	assert_eq!(arena.insert(2).unwrap().generation, usize::MAX);
+}
+
+#[test]
+fn insert_at() {
+	let mut arena = Arena::new(3);
+	let controller = arena.controller();
+	let reserved = controller.try_reserve().unwrap();
+	// success
+	let key = arena
+		.insert_at(reserved.index, reserved.generation, 1)
+		.unwrap();
+	assert_eq!(key, reserved);
+	assert_eq!(arena.get(key), Some(&1));
+	// inserting the same slot and generation again is rejected
+	// and keeps the first item
+	assert_eq!(
+		arena.insert_at(key.index, key.generation, 2),
+		Err(InsertAtError::AlreadyOccupied)
+	);
+	assert_eq!(arena.get(key), Some(&1));
+	// wrong generation
+	assert_eq!(
+		arena.insert_at(key.index, key.generation + 1, 2),
+		Err(InsertAtError::GenerationMismatch)
+	);
+	arena.remove(key);
+	assert_eq!(
+		arena.insert_at(key.index, key.generation, 2),
+		Err(InsertAtError::GenerationMismatch)
+	);
+	// out of bounds
+	assert_eq!(arena.insert_at(3, 0, 2), Err(InsertAtError::OutOfBounds));
+	// free slots that haven't been reserved
+	assert_eq!(arena.insert_at(2, 0, 2), Err(InsertAtError::NotReserved));
+	// retired slots
+	let reserved = controller.try_reserve().unwrap();
+	arena.retire(reserved);
+	assert_eq!(
+		arena.insert_at(reserved.index, reserved.generation + 1, 2),
+		Err(InsertAtError::Retired)
+	);
+	assert!(arena.is_empty());
+	// uninitialized slots start at generation 0
+	let mut arena = Arena::new_lazy(3);
+	let reserved = arena.controller().try_reserve().unwrap();
+	assert_eq!(arena.insert_at(reserved.index, 0, 1), Ok(reserved));
+}
+
+#[test]
+fn repeated_get_after_remove() {
+	let mut arena = Arena::new(2);
+	let key = arena.insert(1).unwrap();
+	for _ in 0..3 {
+		assert_eq!(arena.get(key), Some(&1));
+	}
+	arena.remove(key);
+	assert_eq!(arena.get(key), None);
+	// a new item in the same slot shouldn't be reachable
+	// with the old key
+	let new_key = arena.insert(2).unwrap();
+	assert!(new_key.is_same_slot(&key));
+	assert_eq!(arena.get(key), None);
+	assert_eq!(arena.get(new_key), Some(&2));
+}
+
+#[test]
+fn walk_mut() {
+	let mut arena = Arena::new(3);
+	let a = arena.insert(1).unwrap();
+	let b = arena.insert(2).unwrap();
+	let c = arena.insert(3).unwrap();
+	arena.remove(c);
+	let mut visited = vec![];
+	arena.walk_mut(|key, num, is_live| {
+		visited.push(key);
+		// each item can be changed while checking the others
+		if is_live(b) {
+			*num += 10;
+		}
+		if is_live(c) {
+			*num += 100;
+		}
+		assert!(is_live(key));
+	});
+	assert_eq!(visited, vec![b, a]);
+	assert_eq!(arena.get(a), Some(&11));
+	assert_eq!(arena.get(b), Some(&12));
+}
+
+#[test]
+fn remove_linked() {
+	let mut arena = Arena::new(4);
+	let key1 = arena.insert(1).unwrap();
+	let key2 = arena.insert(2).unwrap();
+	let key3 = arena.insert(3).unwrap();
+	// items are visited newest first, so key3 comes before key2
+	assert_eq!(arena.remove_linked(key2), Some((2, Some(key3), Some(key1))));
+	assert_eq!(
+		arena.iter().map(|(key, _)| key).collect::<Vec<_>>(),
+		vec![key3, key1]
+	);
+	// the ends of the list have no neighbor on one side
+	assert_eq!(arena.remove_linked(key3), Some((3, None, Some(key1))));
+	assert_eq!(arena.remove_linked(key1), Some((1, None, None)));
+	assert_eq!(arena.remove_linked(key1), None);
+}
+
+#[test]
+fn shrink_to_fit() {
+	let mut arena = Arena::new(6);
+	let keys = (0..6).map(|i| arena.insert(i).unwrap()).collect::<Vec<_>>();
+	arena.remove(keys[1]);
+	arena.remove(keys[4]);
+	let remapped = arena.shrink_to_fit();
+	assert_eq!(arena.capacity(), 4);
+	assert_eq!(remapped.len(), 4);
+	for (i, key) in keys.iter().enumerate() {
+		if let Some(new_key) = remapped.get(key) {
+			assert_eq!(arena.get(new_key), Some(&i));
+		}
+	}
+	// items already in the lowest slots keep their keys
+	assert_eq!(remapped[&keys[0]], keys[0]);
+	// old keys for moved items don't point at other items
+	assert_eq!(arena.get(keys[2]), None);
+	assert_eq!(arena.get(keys[3]), None);
+	// items are visited in ascending slot order
+	assert_eq!(
+		arena.iter().map(|(key, _)| key.index).collect::<Vec<_>>(),
+		vec![0, 1, 2, 3]
+	);
+	assert!(arena.insert(6).is_err());
+
+	// the order afterward doesn't depend on how the arena got there
+	let mut arena1 = Arena::new(3);
+	for i in 1..=3 {
+		arena1.insert(i).unwrap();
+	}
+	let mut arena2 = Arena::new(5);
+	let controller = arena2.controller();
+	let temp = controller.try_reserve().unwrap();
+	let keys = (0..3)
+		.map(|_| controller.try_reserve().unwrap())
+		.collect::<Vec<_>>();
+	drop(controller);
+	arena2.insert_with_key(temp, 0).unwrap();
+	arena2.insert_with_key(keys[2], 3).unwrap();
+	arena2.insert_with_key(keys[0], 1).unwrap();
+	arena2.insert_with_key(keys[1], 2).unwrap();
+	arena2.remove(temp);
+	assert_ne!(
+		arena1.iter().map(|(_, num)| *num).collect::<Vec<_>>(),
+		arena2.iter().map(|(_, num)| *num).collect::<Vec<_>>()
+	);
+	arena1.shrink_to_fit();
+	arena2.shrink_to_fit();
+	assert_eq!(
+		arena1.iter().map(|(_, num)| *num).collect::<Vec<_>>(),
+		arena2.iter().map(|(_, num)| *num).collect::<Vec<_>>()
+	);
+}
+
+#[test]
+#[should_panic(expected = "cannot shrink an arena while other controllers for it exist")]
+fn shrink_to_fit_with_controller() {
+	let mut arena = Arena::<()>::new(2);
+	let _controller = arena.controller();
+	arena.shrink_to_fit();
+}
+
+#[test]
+fn try_reserve_detailed() {
+	let mut arena = Arena::new(1);
+	let controller = arena.controller();
+	let (key, recycled) = controller.try_reserve_detailed().unwrap();
+	assert!(!recycled);
+	arena.insert_with_key(key, ()).unwrap();
+	assert_eq!(controller.try_reserve_detailed(), Err(ArenaFull));
+	arena.remove(key);
+	let (new_key, recycled) = controller.try_reserve_detailed().unwrap();
+	assert!(new_key.is_same_slot(&key));
+	assert!(recycled);
+	// a slot with a high generation isn't necessarily recycled
+	let arena = Arena::<()>::new_with_generation_seed(1, |_| 5);
+	let (key, recycled) = arena.controller().try_reserve_detailed().unwrap();
+	assert_eq!(key.generation, 5);
+	assert!(!recycled);
+	// compacting the free list doesn't count as using the slots
+	let mut arena = Arena::<()>::new(2);
+	arena.compact_free_list();
+	arena.reset_free_slot_generations();
+	let controller = arena.controller();
+	assert!(!controller.try_reserve_detailed().unwrap().1);
+	assert!(!controller.try_reserve_detailed().unwrap().1);
+	// a used slot is still recycled after its generation is reset
+	let mut arena = Arena::new(1);
+	let key = arena.insert(()).unwrap();
+	arena.remove(key);
+	arena.reset_free_slot_generations();
+	let (key, recycled) = arena.controller().try_reserve_detailed().unwrap();
+	assert_eq!(key.generation, 0);
+	assert!(recycled);
+}
+
+#[test]
+fn dense_position() {
+	let mut arena = Arena::new(4);
+	let key1 = arena.insert(1).unwrap();
+	let key2 = arena.insert(2).unwrap();
+	let key3 = arena.insert(3).unwrap();
+	assert_eq!(arena.dense_position(key1), Some(0));
+	assert_eq!(arena.dense_position(key2), Some(1));
+	assert_eq!(arena.dense_position(key3), Some(2));
+	// removing other items doesn't change the position
+	arena.remove(key1);
+	assert_eq!(arena.dense_position(key1), None);
+	assert_eq!(arena.dense_position(key2), Some(1));
+	assert_eq!(arena.dense_position(key3), Some(2));
+	// and positions aren't reused, even for the same slot
+	let key4 = arena.insert(4).unwrap();
+	assert!(key4.is_same_slot(&key1));
+	assert_eq!(arena.dense_position(key4), Some(3));
+	arena.update(key2, |num| num * 10).unwrap();
+	assert_eq!(arena.dense_position(key2), Some(1));
+	let key3 = arena.relocate(key3, 3).unwrap();
+	assert_eq!(arena.dense_position(key3), Some(2));
+	// filled arenas number their items in slot order
+	let arena = Arena::filled(3, ());
+	let positions = arena
+		.iter_ordered()
+		.map(|(key, _)| arena.dense_position(key).unwrap())
+		.collect::<Vec<_>>();
+	assert_eq!(positions, vec![0, 1, 2]);
+}
+
+#[test]
+fn new_sharing_controller() {
+	let mut arena1 = Arena::new(3);
+	let mut arena2 = arena1.new_sharing_controller();
+	assert_eq!(arena2.capacity(), 3);
+	assert!(arena2.is_empty());
+	let controller = arena1.controller();
+	// a key reserved once can be inserted into either arena
+	let key1 = controller.try_reserve().unwrap();
+	assert!(arena2.is_reserved(key1));
+	arena2.insert_with_key(key1, 1).unwrap();
+	assert_eq!(arena2.get(key1), Some(&1));
+	assert_eq!(arena1.get(key1), None);
+	// the arenas don't hand out the same slots
+	let key2 = arena1.insert(2).unwrap();
+	assert!(!key2.is_same_slot(&key1));
+	// removing an item frees its slot for both arenas, even though
+	// the other arena hasn't seen the new generation yet
+	arena2.remove(key1);
+	let key3 = controller.try_reserve().unwrap();
+	assert!(key3.is_same_slot(&key1));
+	assert!(arena1.is_reserved(key3));
+	arena1.insert_with_key(key3, 3).unwrap();
+	assert_eq!(arena1.get(key3), Some(&3));
+	assert_eq!(arena1.get(key1), None);
+}
+
+#[test]
+fn changes_since() {
+	let mut arena = Arena::new(6);
+	let key1 = arena.insert(1).unwrap();
+	let key2 = arena.insert(2).unwrap();
+	let key3 = arena.insert(3).unwrap();
+	let token = arena.mark();
+	assert_eq!(arena.changes_since(token), Changes::default());
+	let key4 = arena.insert(4).unwrap();
+	arena.remove(key2);
+	arena.update(key1, |num| num * 10).unwrap();
+	// items inserted and removed after the mark aren't reported
+	let temp = arena.insert(0).unwrap();
+	arena.remove(temp);
+	let key3_moved = arena.relocate(key3, 5).unwrap();
+	let changes = arena.changes_since(token);
+	assert_eq!(changes.inserted, vec![key4, key3_moved]);
+	assert_eq!(changes.removed, vec![key2, key3]);
+	// a new mark starts over
+	let token = arena.mark();
+	arena.remove(key4);
+	assert_eq!(
+		arena.changes_since(token),
+		Changes {
+			inserted: vec![],
+			removed: vec![key4],
+		}
+	);
+}
+
+#[test]
+#[should_panic(expected = "only the most recent change token can be used")]
+fn changes_since_old_token() {
+	let mut arena = Arena::<()>::new(1);
+	let token = arena.mark();
+	arena.mark();
+	arena.changes_since(token);
+}
+
+#[test]
+fn reserve_writer() {
+	let mut arena = Arena::new(1);
+	let (key, writer) = arena.reserve_writer().unwrap();
+	assert!(arena.is_reserved(key));
+	assert!(arena.reserve_writer().is_err());
+	// the writer can be handed to another thread and used later
+	let writer = std::thread::spawn(move || writer).join().unwrap();
+	writer(&mut arena, 1).unwrap();
+	assert_eq!(arena.get(key), Some(&1));
+	arena.remove(key);
+	// dropping the writer frees the reservation
+	let (key, writer) = arena.reserve_writer().unwrap();
+	drop(writer);
+	assert!(!arena.is_reserved(key));
+	let new_key = arena.insert(2).unwrap();
+	assert!(new_key.is_same_slot(&key));
+	assert_ne!(new_key, key);
+	arena.remove(new_key);
+	// so does failing to insert
+	let (key, writer) = arena.reserve_writer().unwrap();
+	let mut other_arena = Arena::new(0);
+	assert_eq!(
+		writer(&mut other_arena, 3),
+		Err(InsertWithKeyError::InvalidKey)
+	);
+	assert!(!arena.is_reserved(key));
+	assert!(arena.insert(4).is_ok());
+}
+
+#[test]
+fn inserts_completed() {
+	const INSERTS: usize = 1000;
+	let mut arena = Arena::new(10);
+	let controller = arena.controller();
+	let monitor = std::thread::spawn(move || {
+		let mut observed = vec![];
+		loop {
+			let count = controller.inserts_completed();
+			observed.push(count);
+			if count == INSERTS {
+				return observed;
+			}
+			std::thread::yield_now();
+		}
+	});
+	for i in 0..INSERTS {
+		let key = arena.insert(i).unwrap();
+		// removing items shouldn't lower the count
+		arena.remove(key);
+	}
+	let observed = monitor.join().unwrap();
+	assert!(observed.windows(2).all(|pair| pair[0] <= pair[1]));
+	assert_eq!(observed.last(), Some(&INSERTS));
+	assert!(arena.is_empty());
+}
+
+#[test]
+fn assert_no_reservation_leaks() {
+	let mut arena = Arena::new(3);
+	arena.assert_no_reservation_leaks();
+	let key = arena.insert(1).unwrap();
+	let reserved = arena.controller().try_reserve().unwrap();
+	arena.insert_with_key(reserved, 2).unwrap();
+	arena.remove(key);
+	arena.assert_no_reservation_leaks();
+}
+
+#[test]
+#[should_panic(expected = "1 slot(s) were reserved but never had an item inserted")]
+fn assert_no_reservation_leaks_with_leak() {
+	let mut arena = Arena::new(3);
+	arena.insert(1).unwrap();
+	arena.controller().try_reserve().unwrap();
+	arena.assert_no_reservation_leaks();
+}
+
+#[test]
+fn transaction() {
+	let mut arena = Arena::new(5);
+	let keys = (0..3).map(|i| arena.insert(i).unwrap()).collect::<Vec<_>>();
+	arena.remove(keys[1]);
+	let snapshot = |arena: &Arena<i32>| {
+		arena
+			.iter()
+			.map(|(key, num)| (key, *num))
+			.collect::<Vec<_>>()
+	};
+	let before = snapshot(&arena);
+	// rolling back undoes everything
+	let mut transaction = arena.transaction();
+	let key3 = transaction.insert(3).unwrap();
+	assert!(transaction.remove(keys[0]));
+	assert!(transaction.remove(keys[2]));
+	assert!(!transaction.remove(keys[1]));
+	let key4 = transaction.insert(4).unwrap();
+	assert!(transaction.remove(key3));
+	assert_eq!(transaction.arena().len(), 1);
+	transaction.rollback();
+	assert_eq!(snapshot(&arena), before);
+	assert_eq!(arena.get(key4), None);
+	assert_eq!(arena.len(), 2);
+	assert!(arena.debug_consistency());
+	// so does dropping the transaction
+	{
+		let mut transaction = arena.transaction();
+		transaction.remove(keys[2]);
+		transaction.insert(5).unwrap();
+	}
+	assert_eq!(snapshot(&arena), before);
+	// committing keeps the changes
+	let mut transaction = arena.transaction();
+	transaction.remove(keys[0]);
+	let key6 = transaction.insert(6).unwrap();
+	transaction.commit();
+	assert_eq!(arena.get(keys[0]), None);
+	assert_eq!(arena.get(key6), Some(&6));
+}
+
+#[test]
+fn transaction_change_tracking() {
+	let mut arena = Arena::new(4);
+	let a = arena.insert(1).unwrap();
+	let b = arena.insert(2).unwrap();
+	let token = arena.mark();
+	// rolled back changes aren't reported
+	let mut transaction = arena.transaction();
+	transaction.remove(a);
+	transaction.insert(3).unwrap();
+	transaction.rollback();
+	assert_eq!(arena.get(a), Some(&1));
+	assert_eq!(arena.changes_since(token), Changes::default());
+	// committed ones are
+	let mut transaction = arena.transaction();
+	transaction.remove(b);
+	let c = transaction.insert(4).unwrap();
+	transaction.commit();
+	assert_eq!(
+		arena.changes_since(token),
+		Changes {
+			inserted: vec![c],
+			removed: vec![b],
+		}
+	);
+}
+
+#[test]
+fn packed_key() {
+	// 32 bits for the slot index, 32 bits for the generation
+	assert_eq!(PackedKey::<32>::GENERATION_BITS, 32);
+	let key = Key {
+		index: 5,
+		generation: 7,
+	};
+	let packed = PackedKey::<32>::new(key).unwrap();
+	assert_eq!(packed.to_bits(), 7 << 32 | 5);
+	assert_eq!(PackedKey::<32>::from_bits(packed.to_bits()).key(), key);
+	// 40 bits for the slot index, 24 bits for the generation
+	assert_eq!(PackedKey::<40>::GENERATION_BITS, 24);
+	assert_eq!(PackedKey::<40>::MAX_SLOT_INDEX, (1 << 40) - 1);
+	assert_eq!(PackedKey::<40>::MAX_GENERATION, (1 << 24) - 1);
+	let key = Key {
+		index: (1 << 40) - 1,
+		generation: (1 << 24) - 1,
+	};
+	let packed = PackedKey::<40>::try_from(key).unwrap();
+	assert_eq!(packed.to_bits(), u64::MAX);
+	assert_eq!(Key::from(PackedKey::<40>::from_bits(packed.to_bits())), key);
+	let key = Key {
+		index: 1 << 40,
+		generation: 0,
+	};
+	assert_eq!(PackedKey::<40>::try_from(key), Err(KeyTooLarge));
+	let key = Key {
+		index: 0,
+		generation: 1 << 24,
+	};
+	assert_eq!(PackedKey::<40>::try_from(key), Err(KeyTooLarge));
+	// keys from an arena round trip
+	let mut arena = Arena::new(3);
+	let keys: Vec<Key> = (0..3).map(|i| arena.insert(i).unwrap()).collect();
+	for key in keys {
+		let packed = PackedKey::<40>::new(key).unwrap();
+		assert_eq!(arena.get(packed.key()), arena.get(key));
+	}
+}
+
+#[test]
+fn get_many_mut_tolerant() {
+	// with one slot, the stale key and the live key share it
+	let mut arena = Arena::new(1);
+	let stale = arena.insert(1).unwrap();
+	arena.remove(stale);
+	let live = arena.insert(2).unwrap();
+	assert!(stale.is_same_slot(&live));
+	assert_eq!(arena.get_disjoint_mut([stale, live, stale]), None);
+	let [a, b, c] = arena.get_many_mut_tolerant([stale, live, stale]);
+	assert_eq!(a, None);
+	assert_eq!(c, None);
+	*b.unwrap() = 3;
+	assert_eq!(arena.get(live), Some(&3));
+}
+
+#[test]
+#[should_panic = "Multiple keys refer to the same slot"]
+fn get_many_mut_tolerant_panics_on_live_duplicates() {
+	let mut arena = Arena::new(1);
+	let key = arena.insert(1).unwrap();
+	arena.get_many_mut_tolerant([key, key]);
+}
+
+#[test]
+fn iter_with_dense_positions() {
+	let mut arena = Arena::new(5);
+	let keys: Vec<Key> = (0..5).map(|i| arena.insert(i).unwrap()).collect();
+	arena.remove(keys[1]);
+	arena.remove(keys[3]);
+	let mut positions: Vec<(Key, usize, i32)> = arena
+		.iter_with_dense_positions()
+		.map(|(key, position, data)| (key, position, *data))
+		.collect();
+	positions.sort_by_key(|(_, position, _)| *position);
+	// positions increase with insertion order and aren't affected
+	// by removing other items
+	assert_eq!(
+		positions,
+		vec![(keys[0], 0, 0), (keys[2], 2, 2), (keys[4], 4, 4)]
+	);
+	// removing everything older than a position
+	let key = arena.insert(5).unwrap();
+	assert_eq!(arena.dense_position(key), Some(5));
+	let old_keys: Vec<Key> = arena
+		.iter_with_dense_positions()
+		.filter(|(_, position, _)| *position < 3)
+		.map(|(key, _, _)| key)
+		.collect();
+	for key in old_keys {
+		arena.remove(key);
+	}
+	let mut remaining: Vec<i32> = arena.iter().map(|(_, data)| *data).collect();
+	remaining.sort_unstable();
+	assert_eq!(remaining, vec![4, 5]);
+}
+
+#[test]
+fn remove_newest_and_oldest() {
+	let mut arena = Arena::new(5);
+	let keys: Vec<Key> = (0..5).map(|i| arena.insert(i).unwrap()).collect();
+	assert_eq!(arena.remove_newest(), Some((keys[4], 4)));
+	assert_eq!(arena.remove_oldest(), Some((keys[0], 0)));
+	assert_eq!(arena.remove_oldest(), Some((keys[1], 1)));
+	assert_eq!(
+		arena.iter().collect::<Vec<_>>(),
+		vec![(keys[3], &3), (keys[2], &2)]
+	);
+	assert_eq!(arena.remove_newest(), Some((keys[3], 3)));
+	assert_eq!(arena.remove_newest(), Some((keys[2], 2)));
+	assert_eq!(arena.remove_newest(), None);
+	assert_eq!(arena.remove_oldest(), None);
+	assert!(arena.debug_consistency());
+}
+
+#[test]
+fn subscribe() {
+	let mut arena = Arena::new(3);
+	let receiver = arena.subscribe();
+	let key = arena.insert(1).unwrap();
+	arena.insert(2).unwrap();
+	arena.remove(key);
+	arena.grow(2);
+	assert_eq!(
+		receiver.try_iter().collect::<Vec<_>>(),
+		vec![(1, 3), (2, 3), (1, 3), (1, 5)]
+	);
+	// dropped receivers are forgotten
+	drop(receiver);
+	arena.insert(3).unwrap();
+	assert!(arena.utilization.senders.is_empty());
+}
+
+#[test]
+fn subscribe_ignores_temporary_changes() {
+	let mut arena = Arena::new(4);
+	let key = arena.insert(1).unwrap();
+	let receiver = arena.subscribe();
+	// updating and relocating an item don't change the length
+	arena.update(key, |i| i + 1).unwrap();
+	let key = arena.relocate(key, 3).unwrap();
+	assert_eq!(receiver.try_iter().count(), 0);
+	// changes made during a transaction are sent, but rolling back
+	// only sends the net change, which is nothing here
+	let mut transaction = arena.transaction();
+	transaction.insert(2).unwrap();
+	transaction.remove(key);
+	transaction.rollback();
+	assert_eq!(
+		receiver.try_iter().collect::<Vec<_>>(),
+		vec![(2, 4), (1, 4)]
+	);
+	// a panicking update removes the item
+	let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+		arena.update(key, |_| panic!()).unwrap();
+	}));
+	assert!(result.is_err());
+	assert_eq!(receiver.try_iter().collect::<Vec<_>>(), vec![(0, 4)]);
+}
+
+#[test]
+fn retain_frees_slots_like_remove() {
+	fn build() -> (Arena<usize>, Vec<Key>) {
+		let mut arena = Arena::new(10);
+		let keys = (0..10).map(|i| arena.insert(i).unwrap()).collect();
+		(arena, keys)
+	}
+	let (mut retained, _) = build();
+	retained.retain(|i| i % 3 == 0);
+	let (mut removed, keys) = build();
+	// remove in the same order retain visits the items
+	for key in keys.iter().rev() {
+		if removed[*key] % 3 != 0 {
+			removed.remove(*key);
+		}
+	}
+	assert_eq!(
+		retained.iter().collect::<Vec<_>>(),
+		removed.iter().collect::<Vec<_>>()
+	);
+	// the freed slots are reserved in the same order with the
+	// same generations
+	for _ in 0..6 {
+		assert_eq!(
+			retained.controller().try_reserve(),
+			removed.controller().try_reserve()
+		);
+	}
+	assert!(retained.controller().try_reserve().is_err());
+	assert!(retained.debug_consistency());
+}
+
+#[test]
+fn retain_frees_slots_when_predicate_panics() {
+	let mut arena = Arena::new(4);
+	for i in 0..4 {
+		arena.insert(i).unwrap();
+	}
+	let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+		arena.retain(|i| {
+			assert_ne!(*i, 1);
+			*i != 2
+		})
+	}));
+	assert!(result.is_err());
+	// 3 and 2 were visited before the panic, and 2 was removed
+	assert_eq!(arena.len(), 3);
+	assert!(arena.controller().try_reserve().is_ok());
+	assert!(arena.debug_consistency());
+}
+
+#[test]
+fn is_slot_occupied() {
+	let mut arena = Arena::new(4);
+	let keys: Vec<Key> = (0..3).map(|i| arena.insert(i).unwrap()).collect();
+	assert!(arena.is_slot_occupied(keys[0].index));
+	assert!(arena.is_slot_occupied(keys[1].index));
+	assert!(arena.is_slot_occupied(keys[2].index));
+	assert!(!arena.is_slot_occupied(3));
+	assert!(!arena.is_slot_occupied(100));
+	arena.remove(keys[1]);
+	assert!(arena.is_slot_occupied(keys[0].index));
+	assert!(!arena.is_slot_occupied(keys[1].index));
+	assert!(arena.is_slot_occupied(keys[2].index));
+	// reserved slots aren't occupied until an item is inserted
+	let key = arena.controller().try_reserve().unwrap();
+	assert!(!arena.is_slot_occupied(key.index));
+	arena.insert_with_key(key, 3).unwrap();
+	assert!(arena.is_slot_occupied(key.index));
+}
+
+#[test]
+fn extend_from_slice() {
+	let mut arena = Arena::new(5);
+	let keys = arena.extend_from_slice(&[1, 2, 3]).unwrap();
+	assert_eq!(keys.len(), 3);
+	assert_eq!(
+		keys.iter().map(|key| arena[*key]).collect::<Vec<_>>(),
+		vec![1, 2, 3]
+	);
+	// nothing is inserted if there isn't room for everything
+	assert_eq!(arena.extend_from_slice(&[4, 5, 6]), Err(ArenaFull));
+	assert_eq!(arena.len(), 3);
+	assert_eq!(arena.controller().free_count(), 2);
+	// including when the last free slots are reserved for
+	// priority reservations
+	let mut arena = Arena::new_with_reserved_lane(3, 1);
+	assert_eq!(arena.extend_from_slice(&[1, 2, 3]), Err(ArenaFull));
+	assert!(arena.is_empty());
+	assert!(arena.debug_consistency());
+	assert_eq!(arena.extend_from_slice(&[1, 2]).unwrap().len(), 2);
+}
+
+#[test]
+fn get_mut_tracked() {
+	let mut arena = Arena::new(4);
+	let keys: Vec<Key> = (0..4).map(|i| arena.insert(i).unwrap()).collect();
+	*arena.get_mut_tracked(keys[2]).unwrap() += 10;
+	*arena.get_mut_tracked(keys[0]).unwrap() += 10;
+	*arena.get_mut_tracked(keys[2]).unwrap() += 10;
+	{
+		let guard = arena.get_mut_tracked(keys[3]).unwrap();
+		assert_eq!(guard.key(), keys[3]);
+	}
+	arena.remove(keys[3]);
+	assert!(arena.get_mut_tracked(keys[3]).is_none());
+	assert_eq!(arena[keys[0]], 10);
+	assert_eq!(arena[keys[2]], 22);
+	// removed items are left out, and each key is only listed once
+	let mut expected = vec![keys[0], keys[2]];
+	expected.sort_unstable_by_key(|key| key.index);
+	assert_eq!(arena.take_dirty(), expected);
+	assert_eq!(arena.take_dirty(), vec![]);
+}
+
+#[test]
+fn sorted_keys_by() {
+	let mut arena = Arena::new(5);
+	let keys: Vec<Key> = [3, 1, 4, 1, 5]
+		.iter()
+		.map(|i| arena.insert(*i).unwrap())
+		.collect();
+	let before: Vec<Key> = arena.iter().map(|(key, _)| key).collect();
+	// sort by value, descending
+	let sorted = arena.sorted_keys_by(|i| std::cmp::Reverse(*i));
+	assert_eq!(
+		sorted.iter().map(|key| arena[*key]).collect::<Vec<_>>(),
+		vec![5, 4, 3, 1, 1]
+	);
+	// equal items keep their iteration order
+	assert_eq!(&sorted[3..], &[keys[3], keys[1]]);
+	// the arena isn't changed
+	assert_eq!(arena.iter().map(|(key, _)| key).collect::<Vec<_>>(), before);
+}
+
+#[test]
+fn predicates_can_be_stateful() {
+	let mut arena = Arena::new(4);
+	for i in 0..4 {
+		arena.insert(i).unwrap();
+	}
+	let mut calls = 0;
+	assert_eq!(
+		arena.count(|i| {
+			calls += 1;
+			i % 2 == 0
+		}),
+		2
+	);
+	assert_eq!(calls, 4);
+	let mut seen = vec![];
+	assert!(!arena.any(|i| {
+		seen.push(*i);
+		false
+	}));
+	assert!(arena.all(|i| {
+		seen.push(*i);
+		true
+	}));
+	assert_eq!(seen.len(), 8);
+	let mut calls = 0;
+	arena.find(|_| {
+		calls += 1;
+		calls == 2
+	});
+	assert_eq!(calls, 2);
+}
+
+#[test]
+fn generation_wraparound() {
+	let mut arena = Arena::from_parts(ArenaParts {
+		slots: vec![SlotParts {
+			generation: usize::MAX,
+			data: Some(1),
+		}],
+		free_list: vec![],
+		occupied_list: vec![0],
+		reservation_order: ReservationOrder::default(),
+	})
+	.unwrap();
+	let old_key = arena.iter().next().unwrap().0;
+	assert_eq!(old_key.generation, usize::MAX);
+	assert_eq!(arena.remove(old_key), Some(1));
+	// the generation wraps around to 0
+	let key = arena.insert(2).unwrap();
+	assert_eq!(key.generation, 0);
+	assert_eq!(arena.get(old_key), None);
+	assert_eq!(arena.get(key), Some(&2));
+	assert!(arena.debug_consistency());
+	// a key from before the wraparound becomes valid again
+	let stale_key = Key {
+		index: 0,
+		generation: 0,
+	};
+	assert_eq!(arena.get(stale_key), Some(&2));
+}
+
+#[test]
+fn dropped_writer_generation_survives_parts() {
+	let arena = Arena::<i32>::new(1);
+	let (key, writer) = arena.reserve_writer().unwrap();
+	drop(writer);
+	assert_eq!(arena.debug_slot(key.index).unwrap().generation(), 1);
+	assert_eq!(arena.generation_stats().max, 1);
+	// rebuilding the arena keeps the generation the slot was
+	// freed with, so the released key doesn't become valid
+	let mut arena = Arena::from_parts(arena.into_parts()).unwrap();
+	let new_key = arena.insert(1).unwrap();
+	assert!(new_key.is_same_slot(&key));
+	assert_ne!(new_key, key);
+	assert_eq!(arena.get(key), None);
+}
+
+#[test]
+fn extend_from_slice_failure_leaves_arena_unchanged() {
+	// the last slot can only be used by priority reservations,
+	// so there's only room for two items
+	let mut arena = Arena::new_with_reserved_lane(3, 1);
+	let free_list_order = arena.controller().free_list_order();
+	assert_eq!(arena.extend_from_slice(&[1, 2, 3]), Err(ArenaFull));
+	assert_eq!(arena.controller().free_list_order(), free_list_order);
+	let parts = arena.into_parts();
+	assert_eq!(
+		parts
+			.slots
+			.iter()
+			.map(|slot| slot.generation)
+			.collect::<Vec<_>>(),
+		vec![0, 0, 0]
+	);
+	let mut arena = Arena::from_parts(parts).unwrap();
+	let key = arena.insert(1).unwrap();
+	assert_eq!(key.generation, 0);
+}
+
+#[test]
+fn transaction_holds_removed_slots() {
+	let mut arena = Arena::new(1);
+	let key = arena.insert(1).unwrap();
+	let controller = arena.controller();
+	// the removed item's slot can't be reserved by anyone else
+	// until the transaction is committed
+	let mut transaction = arena.transaction();
+	assert!(transaction.remove(key));
+	assert_eq!(controller.try_reserve(), Err(ArenaFull));
+	transaction.rollback();
+	assert_eq!(arena.get(key), Some(&1));
+	assert!(arena.debug_consistency());
+	let mut transaction = arena.transaction();
+	transaction.remove(key);
+	transaction.commit();
+	let new_key = controller.try_reserve().unwrap();
+	assert!(new_key.is_same_slot(&key));
+	assert_eq!(new_key.generation, key.generation + 1);
+	// rolling back while unwinding from a panic doesn't panic again
+	arena.insert_with_key(new_key, 2).unwrap();
+	let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+		let mut transaction = arena.transaction();
+		transaction.remove(new_key);
+		panic!();
+	}));
+	assert!(result.is_err());
+	assert_eq!(arena.get(new_key), Some(&2));
+	assert!(arena.debug_consistency());
+}
@@ -0,0 +1,157 @@
+//! Grouping changes to an [`Arena`] so they can be undone.
+
+use crate::{error::ArenaFull, Arena, Key};
+
+/// A change made to an [`Arena`] during a [`Transaction`].
+enum Change<T> {
+	Inserted(Key),
+	Removed {
+		key: Key,
+		data: T,
+		previous_occupied_slot_index: Option<usize>,
+		next_occupied_slot_index: Option<usize>,
+		dense_position: usize,
+		inserted_version: usize,
+	},
+}
+
+/// A group of inserts and removals that can be undone together.
+///
+/// Changes are applied to the [`Arena`] right away.
+/// [`commit`](Self::commit) keeps them, while
+/// [`rollback`](Self::rollback) undoes them in reverse order.
+/// Dropping the [`Transaction`] without calling either rolls
+/// it back.
+///
+/// Rolling back gives removed items their old [`Key`]s and
+/// places in the iteration order back. [`Key`]s for items
+/// inserted during the transaction should be discarded, since
+/// they may become valid again for other items later.
+///
+/// The slots of removed items stay reserved until the
+/// [`Transaction`] is committed, so they can always be restored.
+///
+/// Returned by [`Arena::transaction`].
+pub struct Transaction<'a, T> {
+	arena: &'a mut Arena<T>,
+	changes: Vec<Change<T>>,
+}
+
+impl<'a, T> Transaction<'a, T> {
+	pub(super) fn new(arena: &'a mut Arena<T>) -> Self {
+		Self {
+			arena,
+			changes: vec![],
+		}
+	}
+
+	/// Returns a shared reference to the [`Arena`].
+	pub fn arena(&self) -> &Arena<T> {
+		self.arena
+	}
+
+	/// Tries to insert an item into the [`Arena`], and, if
+	/// successful, returns its [`Key`].
+	pub fn insert(&mut self, data: T) -> Result<Key, ArenaFull> {
+		let key = self.arena.insert(data)?;
+		self.changes.push(Change::Inserted(key));
+		Ok(key)
+	}
+
+	/// If the [`Arena`] contains an item with the given [`Key`],
+	/// removes it and returns `true`. Otherwise, returns `false`.
+	///
+	/// The item is kept by the [`Transaction`] in case it's
+	/// rolled back.
+	pub fn remove(&mut self, key: Key) -> bool {
+		if !self.arena.contains(key) {
+			return false;
+		}
+		let slot = &self.arena.slots[key.index];
+		let previous_occupied_slot_index = slot.previous_occupied_slot_index();
+		let next_occupied_slot_index = slot.next_occupied_slot_index();
+		let dense_position = slot.dense_position;
+		let inserted_version = slot.inserted_version;
+		// the removal is only logged once the transaction commits,
+		// since rolling back brings the item back
+		let data = self
+			.arena
+			.unlink_slot(key.index)
+			.expect("the key was checked to be valid");
+		// keep the slot reserved in case the removal is rolled back,
+		// but bump its generation like removing it normally would
+		let generation = self.arena.slots[key.index].generation;
+		self.arena.set_slot_generation(key.index, generation);
+		self.changes.push(Change::Removed {
+			key,
+			data,
+			previous_occupied_slot_index,
+			next_occupied_slot_index,
+			dense_position,
+			inserted_version,
+		});
+		true
+	}
+
+	/// Keeps the changes made during the [`Transaction`] and drops
+	/// the removed items.
+	pub fn commit(mut self) {
+		for change in &self.changes {
+			if let Change::Removed {
+				key,
+				inserted_version,
+				..
+			} = change
+			{
+				self.arena.changes.log_removal(*key, *inserted_version);
+				self.arena.controller.release(key.index);
+			}
+		}
+		self.changes.clear();
+	}
+
+	/// Undoes the changes made during the [`Transaction`].
+	pub fn rollback(self) {
+		// undoing happens when the transaction is dropped
+	}
+
+	fn undo(&mut self) {
+		// subscribers only see the net change
+		self.arena.pause_utilization_updates();
+		while let Some(change) = self.changes.pop() {
+			match change {
+				Change::Inserted(key) => {
+					self.arena.remove(key);
+				}
+				Change::Removed {
+					key,
+					data,
+					previous_occupied_slot_index,
+					next_occupied_slot_index,
+					dense_position,
+					inserted_version,
+				} => {
+					// the slot is still reserved, so rewinding the
+					// generation makes the old key valid again
+					self.arena.set_slot_generation(key.index, key.generation);
+					let slot = &mut self.arena.slots[key.index];
+					slot.dense_position = dense_position;
+					slot.inserted_version = inserted_version;
+					self.arena.relink_slot(
+						key.index,
+						data,
+						previous_occupied_slot_index,
+						next_occupied_slot_index,
+					);
+				}
+			}
+		}
+		self.arena.resume_utilization_updates();
+	}
+}
+
+impl<T> Drop for Transaction<'_, T> {
+	fn drop(&mut self) {
+		self.undo();
+	}
+}